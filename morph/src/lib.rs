@@ -18,10 +18,12 @@ struct LinderaToken {
 // https://gist.github.com/masayu-a/3e11168f9330e2d83a68
 // https://gist.github.com/masayu-a/b3ce862336e47736e84f
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub struct Morpheme {
     pub text: String,
     pub lemma: String,
+    /// the written form of the lemma, e.g. 食べる for 食べ in 食べた
+    pub orth_base: String,
     pub pronounciation: String,
     pub part_of_speech: String,
     pub inflection_type: Option<String>,
@@ -116,6 +118,7 @@ pub fn categorize(details: Vec<String>) -> Option<Morpheme> {
     let text = details[8].to_owned();
     // caution, sometimes it's not just the lemma, e.g. '君' gives '君-代名詞', redundant with PoS
     let lemma = details[7].to_owned();
+    let orth_base = details[10].to_owned();
     let pronounciation = details[6].to_owned();
     let inflection_type = Some(details[4].to_owned()).filter(|s| *s != "*");
     let inflection_form = Some(details[5].to_owned()).filter(|s| *s != "*");
@@ -129,6 +132,7 @@ pub fn categorize(details: Vec<String>) -> Option<Morpheme> {
     Some(Morpheme {
         text,
         lemma,
+        orth_base,
         pronounciation,
         part_of_speech,
         inflection_type,
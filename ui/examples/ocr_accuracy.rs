@@ -0,0 +1,116 @@
+//! Character error rate of the OCR and analysis pipeline over sample images, per preprocessing configuration.
+//!
+//! Every image of the directory (`examples` by default) having a `.txt` sidecar with the same stem is recognized,
+//! and the sidecar's content is the expected text, e.g. `ffix_sample.png` and `ffix_sample.txt`. Fails when the
+//! mean error rate of the configured preprocessing is above the maximum (0.1 by default), so that regressions show.
+//!
+//! The Lindera server of the configuration has to be running.
+//!
+//! `cargo run --example ocr_accuracy [directory] [maximum error rate]`
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use kanjisabi::config::{load_config, Preproc};
+use kanjisabi::ocr::eval::{char_error_rate, recognized_text};
+use kanjisabi::ocr::jpn::JpnOCR;
+use kanjisabi::ocr::layout::LayoutParams;
+use kanjisabi::pipeline::recognize_capture;
+use kanjisabi::script::ScriptPolicy;
+use morph::JpnMorphAnalysisAPI;
+
+fn samples(dir: &Path) -> Result<Vec<(PathBuf, String)>> {
+    let mut samples = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("png") {
+            continue;
+        }
+        if let Ok(expected) = std::fs::read_to_string(path.with_extension("txt")) {
+            samples.push((path, expected));
+        }
+    }
+    samples.sort();
+    Ok(samples)
+}
+
+/// the configured preprocessing first, then its variations in contrast and deskewing
+fn configurations(configured: &Preproc) -> Vec<(String, Preproc)> {
+    let mut configurations = vec![(
+        "configured".to_owned(),
+        Preproc {
+            contrast: configured.contrast,
+            deskew: configured.deskew,
+            max_skew_angle: configured.max_skew_angle,
+        },
+    )];
+    for contrast in [0., 50., 100., 200.] {
+        for deskew in [false, true] {
+            configurations.push((
+                format!("contrast {}, deskew {}", contrast, deskew),
+                Preproc {
+                    contrast,
+                    deskew,
+                    max_skew_angle: configured.max_skew_angle,
+                },
+            ));
+        }
+    }
+    configurations
+}
+
+pub fn main() -> Result<()> {
+    let dir = std::env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("examples"));
+    let max_error_rate: f32 = match std::env::args().nth(2) {
+        Some(arg) => arg.parse()?,
+        None => 0.1,
+    };
+
+    let samples = samples(&dir)?;
+    if samples.is_empty() {
+        println!("no image with an expected text sidecar in {:?}", dir);
+        return Ok(());
+    }
+
+    let config = load_config().unwrap_or_default();
+    let morph_api = JpnMorphAnalysisAPI::with_lindera_address(&config.lindera.server_address)?;
+    let mut ocr = JpnOCR::new(morph_api);
+    ocr.set_script_policy(ScriptPolicy::from(&config.script));
+    ocr.set_layout_params(LayoutParams::from(&config.layout));
+
+    let mut configured_error_rate = 0.;
+    for (name, preproc) in configurations(&config.preproc) {
+        println!("{}", name);
+        let mut total = 0.;
+        for (path, expected) in &samples {
+            let img = image::open(path)?.to_rgba8();
+            let jpn_texts = recognize_capture(&mut ocr, img, &preproc)?;
+            let actual = recognized_text(&jpn_texts);
+            let cer = char_error_rate(expected, &actual);
+            total += cer;
+            println!(
+                "\t{:.3}\t{}\t{:?}",
+                cer,
+                path.file_name().unwrap_or_default().to_string_lossy(),
+                actual
+            );
+        }
+        let mean = total / samples.len() as f32;
+        println!("\t{:.3}\tmean", mean);
+        if name == "configured" {
+            configured_error_rate = mean;
+        }
+    }
+
+    if configured_error_rate > max_error_rate {
+        return Err(anyhow!(
+            "mean error rate {:.3} of the configured preprocessing above {:.3}",
+            configured_error_rate,
+            max_error_rate
+        ));
+    }
+    Ok(())
+}
@@ -1,11 +1,9 @@
 extern crate device_query;
-extern crate screenshot;
-extern crate tesseract;
 
 use anyhow::Result;
 use device_query::{DeviceQuery, DeviceState};
 use kanjisabi::ocr::OCR;
-use screenshot::get_screenshot_area;
+use kanjisabi::screen::capture::ScreenCapture;
 use std::time;
 
 pub fn main() -> Result<()> {
@@ -18,13 +16,14 @@ pub fn main() -> Result<()> {
 
     let device_state = DeviceState::new();
 
+    let (conn, screen_num) = x11rb::connect(None)?;
+    let mut screen_capture = ScreenCapture::new(&conn, screen_num)?;
+
     let mut mouse_pos = device_state.get_mouse().coords;
 
     let mut elapsed_ticks_since_mouse_moved = 0;
 
-    let ocr = OCR {
-        lang: String::from("eng"),
-    };
+    let mut ocr = OCR::new("eng");
 
     loop {
         let pos = device_state.get_mouse().coords;
@@ -45,18 +44,18 @@ pub fn main() -> Result<()> {
             let y = std::cmp::max(0, mouse_pos.1 - capture_h);
             let w = capture_w;
             let h = mouse_pos.1.clamp(1, capture_h);
-            let ocr_area = get_screenshot_area(0, x as u32, y as u32, w as u32, h as u32).unwrap();
+            let ocr_area = screen_capture.capture(&conn, x, y, w as u32, h as u32)?;
 
             println!("running OCR...");
 
             println!(
                 "{:?}",
                 ocr.recognize_words(
-                    ocr_area.as_ref(),
+                    ocr_area.as_raw(),
                     ocr_area.width() as i32,
                     ocr_area.height() as i32,
-                    ocr_area.pixel_width() as i32,
-                    ocr_area.pixel_width() as i32 * ocr_area.width() as i32,
+                    4,
+                    4 * ocr_area.width() as i32,
                 )
                 .unwrap_or_default()
             );
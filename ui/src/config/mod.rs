@@ -9,6 +9,12 @@ use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
 use serde::{de::Error, Deserialize, Deserializer};
 use serde_with::{serde_as, DeserializeAs};
 
+use crate::dict::LookupParams;
+use crate::ocr::layout::LayoutParams;
+use crate::pin::PinParams;
+use crate::region::RegionParams;
+use crate::script::ScriptPolicy;
+
 const CONFIG_FILE: &str = "kanjisabi.toml";
 
 pub fn load_config() -> Result<KSConfig> {
@@ -24,6 +30,17 @@ pub fn load_config() -> Result<KSConfig> {
             warn!("Incompatible configuration: {:?}", e);
             anyhow!("Incompatible configuration: {:?}", e)
         })
+        .inspect(|config: &KSConfig| {
+            let missing = LookupParams::from(&config.glosses)
+                .missing_languages()
+                .join(", ");
+            if !missing.is_empty() {
+                warn!(
+                    "Glosses in {} are not built in, see the translations-* features",
+                    missing
+                );
+            }
+        })
 }
 
 pub fn watch_config() -> Result<(Receiver<()>, RecommendedWatcher)> {
@@ -61,6 +78,32 @@ pub struct KSConfig {
     pub colors: Colors,
     #[serde(default = "Preproc::default")]
     pub preproc: Preproc,
+    #[serde(default = "Script::default")]
+    pub script: Script,
+    #[serde(default = "Layout::default")]
+    pub layout: Layout,
+    #[serde(default = "Cache::default")]
+    pub cache: Cache,
+    #[serde(default = "Export::default")]
+    pub export: Export,
+    #[serde(default = "Region::default")]
+    pub region: Region,
+    #[serde(default = "Hover::default")]
+    pub hover: Hover,
+    #[serde(default = "Capture::default")]
+    pub capture: Capture,
+    #[serde(default = "Pin::default")]
+    pub pin: Pin,
+    #[serde(default = "Anchor::default")]
+    pub anchor: Anchor,
+    #[serde(default = "Glosses::default")]
+    pub glosses: Glosses,
+    #[serde(default = "Kanji::default")]
+    pub kanji: Kanji,
+    #[serde(default = "Names::default")]
+    pub names: Names,
+    #[serde(default = "Yomitan::default")]
+    pub yomitan: Yomitan,
     #[serde(default = "Keys::default")]
     pub keys: Keys,
 }
@@ -150,20 +193,507 @@ fn default_contrast() -> f32 {
     100.
 }
 
+fn default_deskew() -> bool {
+    true
+}
+
+fn default_max_skew_angle() -> f32 {
+    10.
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Preproc {
     #[serde(default = "default_contrast")]
     pub contrast: f32,
+    #[serde(default = "default_deskew")]
+    pub deskew: bool,
+    #[serde(default = "default_max_skew_angle")]
+    pub max_skew_angle: f32,
 }
 
 impl Default for Preproc {
     fn default() -> Self {
         Self {
             contrast: default_contrast(),
+            deskew: default_deskew(),
+            max_skew_angle: default_max_skew_angle(),
+        }
+    }
+}
+
+// script
+
+fn default_allow() -> bool {
+    true
+}
+
+fn default_latin() -> bool {
+    false
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Script {
+    #[serde(default = "default_allow")]
+    pub digits: bool,
+    #[serde(default = "default_allow")]
+    pub punctuation: bool,
+    #[serde(default = "default_allow")]
+    pub prolonged_marks: bool,
+    #[serde(default = "default_latin")]
+    pub latin: bool,
+}
+
+impl Default for Script {
+    fn default() -> Self {
+        Self {
+            digits: default_allow(),
+            punctuation: default_allow(),
+            prolonged_marks: default_allow(),
+            latin: default_latin(),
+        }
+    }
+}
+
+impl From<&Script> for ScriptPolicy {
+    fn from(script: &Script) -> Self {
+        Self {
+            digits: script.digits,
+            punctuation: script.punctuation,
+            prolonged_marks: script.prolonged_marks,
+            latin: script.latin,
+        }
+    }
+}
+
+// layout
+
+fn default_join_lines() -> bool {
+    true
+}
+
+fn default_max_line_gap() -> f32 {
+    1.
+}
+
+fn default_ruby_ratio() -> f32 {
+    0.6
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Layout {
+    #[serde(default = "default_join_lines")]
+    pub join_lines: bool,
+    #[serde(default = "default_max_line_gap")]
+    pub max_line_gap: f32,
+    #[serde(default = "default_ruby_ratio")]
+    pub ruby_ratio: f32,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            join_lines: default_join_lines(),
+            max_line_gap: default_max_line_gap(),
+            ruby_ratio: default_ruby_ratio(),
+        }
+    }
+}
+
+impl From<&Layout> for LayoutParams {
+    fn from(layout: &Layout) -> Self {
+        Self {
+            join_lines: layout.join_lines,
+            max_line_gap: layout.max_line_gap,
+            ruby_ratio: layout.ruby_ratio,
+        }
+    }
+}
+
+// cache
+
+fn default_cache_capacity() -> usize {
+    32
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Cache {
+    #[serde(default = "default_cache_capacity")]
+    pub capacity: usize,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self {
+            capacity: default_cache_capacity(),
+        }
+    }
+}
+
+// export
+
+fn default_export_directory() -> Option<String> {
+    None
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Export {
+    #[serde(default = "default_export_directory")]
+    pub directory: Option<String>,
+}
+
+impl Export {
+    /// `$XDG_DATA_HOME/kanjisabi/exports` unless configured otherwise
+    pub fn directory(&self) -> PathBuf {
+        match &self.directory {
+            Some(directory) if !directory.is_empty() => PathBuf::from(directory),
+            _ => {
+                let mut path = BaseDirs::new().unwrap().data_dir().to_path_buf();
+                path.push("kanjisabi");
+                path.push("exports");
+                path
+            }
+        }
+    }
+}
+
+// text region detection
+
+fn default_search_width() -> u32 {
+    800
+}
+
+fn default_search_height() -> u32 {
+    600
+}
+
+fn default_max_gap() -> f32 {
+    1.
+}
+
+fn default_max_glyph_size() -> f32 {
+    1.5
+}
+
+fn default_background_tolerance() -> i32 {
+    24
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Region {
+    #[serde(default = "default_search_width")]
+    pub search_width: u32,
+    #[serde(default = "default_search_height")]
+    pub search_height: u32,
+    #[serde(default = "default_max_gap")]
+    pub max_gap: f32,
+    #[serde(default = "default_max_glyph_size")]
+    pub max_glyph_size: f32,
+    #[serde(default = "default_background_tolerance")]
+    pub background_tolerance: i32,
+}
+
+impl Default for Region {
+    fn default() -> Self {
+        Self {
+            search_width: default_search_width(),
+            search_height: default_search_height(),
+            max_gap: default_max_gap(),
+            max_glyph_size: default_max_glyph_size(),
+            background_tolerance: default_background_tolerance(),
+        }
+    }
+}
+
+impl From<&Region> for RegionParams {
+    fn from(region: &Region) -> Self {
+        Self {
+            max_gap: region.max_gap,
+            max_glyph_size: region.max_glyph_size,
+            background_tolerance: region.background_tolerance,
+        }
+    }
+}
+
+// hover mode
+
+fn default_hover_enabled() -> bool {
+    false
+}
+
+fn default_dwell_time() -> u64 {
+    500
+}
+
+fn default_hover_width() -> u32 {
+    400
+}
+
+fn default_hover_height() -> u32 {
+    100
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Hover {
+    #[serde(default = "default_hover_enabled")]
+    pub enabled: bool,
+    /// milliseconds
+    #[serde(default = "default_dwell_time")]
+    pub dwell_time: u64,
+    #[serde(default = "default_hover_width")]
+    pub width: u32,
+    #[serde(default = "default_hover_height")]
+    pub height: u32,
+}
+
+impl Default for Hover {
+    fn default() -> Self {
+        Self {
+            enabled: default_hover_enabled(),
+            dwell_time: default_dwell_time(),
+            width: default_hover_width(),
+            height: default_hover_height(),
+        }
+    }
+}
+
+// screen captures
+
+fn default_compositor_delay() -> u64 {
+    30
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Capture {
+    /// milliseconds for a compositor to repaint the screen once the overlay is hidden, before capturing
+    #[serde(default = "default_compositor_delay")]
+    pub compositor_delay: u64,
+}
+
+impl Default for Capture {
+    fn default() -> Self {
+        Self {
+            compositor_delay: default_compositor_delay(),
         }
     }
 }
 
+// pinned region
+
+fn default_change_threshold() -> f32 {
+    0.01
+}
+
+fn default_debounce() -> u64 {
+    300
+}
+
+fn default_poll_interval() -> u64 {
+    200
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Pin {
+    /// fraction of the region's pixels
+    #[serde(default = "default_change_threshold")]
+    pub change_threshold: f32,
+    /// milliseconds
+    #[serde(default = "default_debounce")]
+    pub debounce: u64,
+    /// milliseconds
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+}
+
+impl Default for Pin {
+    fn default() -> Self {
+        Self {
+            change_threshold: default_change_threshold(),
+            debounce: default_debounce(),
+            poll_interval: default_poll_interval(),
+        }
+    }
+}
+
+impl From<&Pin> for PinParams {
+    fn from(pin: &Pin) -> Self {
+        Self {
+            change_threshold: pin.change_threshold,
+            debounce: Duration::from_millis(pin.debounce),
+        }
+    }
+}
+
+// window-anchored capture area
+
+fn default_anchor_window() -> Option<String> {
+    None
+}
+
+fn default_pick_timeout() -> u64 {
+    10000
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Anchor {
+    /// WM_CLASS or name of the window; picked by clicking if empty
+    #[serde(default = "default_anchor_window")]
+    pub window: Option<String>,
+    /// milliseconds to click the window in, before picking is given up
+    #[serde(default = "default_pick_timeout")]
+    pub pick_timeout: u64,
+}
+
+impl Default for Anchor {
+    fn default() -> Self {
+        Self {
+            window: default_anchor_window(),
+            pick_timeout: default_pick_timeout(),
+        }
+    }
+}
+
+impl Anchor {
+    pub fn window(&self) -> Option<&str> {
+        self.window.as_deref().filter(|window| !window.is_empty())
+    }
+}
+
+// dictionary glosses
+
+fn default_max_senses() -> usize {
+    3
+}
+
+fn default_obsolete_senses() -> bool {
+    false
+}
+
+fn default_languages() -> Vec<String> {
+    vec!["eng".to_owned()]
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Glosses {
+    /// senses shown at once, the others are paged through
+    #[serde(default = "default_max_senses")]
+    pub max_senses: usize,
+    /// senses marked obsolete or archaic
+    #[serde(default = "default_obsolete_senses")]
+    pub obsolete_senses: bool,
+    /// languages of the glosses, English standing in for senses not translated in any of them
+    #[serde(default = "default_languages")]
+    pub languages: Vec<String>,
+}
+
+impl Default for Glosses {
+    fn default() -> Self {
+        Self {
+            max_senses: default_max_senses(),
+            obsolete_senses: default_obsolete_senses(),
+            languages: default_languages(),
+        }
+    }
+}
+
+impl From<&Glosses> for LookupParams {
+    fn from(glosses: &Glosses) -> Self {
+        Self {
+            obsolete_senses: glosses.obsolete_senses,
+            languages: glosses.languages.clone(),
+        }
+    }
+}
+
+// kanji information
+
+fn default_kanji_file() -> Option<String> {
+    None
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Kanji {
+    /// path to KANJIDIC2's XML file
+    #[serde(default = "default_kanji_file")]
+    pub kanjidic: Option<String>,
+    /// path to KRADFILE
+    #[serde(default = "default_kanji_file")]
+    pub kradfile: Option<String>,
+    /// path to RADKFILE
+    #[serde(default = "default_kanji_file")]
+    pub radkfile: Option<String>,
+}
+
+fn file_path(path: &Option<String>) -> Option<PathBuf> {
+    path.as_deref()
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+impl Kanji {
+    pub fn kanjidic(&self) -> Option<PathBuf> {
+        file_path(&self.kanjidic)
+    }
+
+    pub fn kradfile(&self) -> Option<PathBuf> {
+        file_path(&self.kradfile)
+    }
+
+    pub fn radkfile(&self) -> Option<PathBuf> {
+        file_path(&self.radkfile)
+    }
+}
+
+// proper names
+
+fn default_jmnedict() -> Option<String> {
+    None
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Names {
+    /// path to JMnedict's XML file
+    #[serde(default = "default_jmnedict")]
+    pub jmnedict: Option<String>,
+}
+
+impl Names {
+    pub fn jmnedict(&self) -> Option<PathBuf> {
+        file_path(&self.jmnedict)
+    }
+}
+
+// Yomitan dictionaries
+
+fn default_dictionary_enabled() -> bool {
+    true
+}
+
+fn default_dictionary_priority() -> i32 {
+    0
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct YomitanSource {
+    /// path to the dictionary's zip archive
+    pub path: String,
+    #[serde(default = "default_dictionary_enabled")]
+    pub enabled: bool,
+    /// dictionaries with a positive priority are consulted before JMdict, the others after it, the highest
+    /// first
+    #[serde(default = "default_dictionary_priority")]
+    pub priority: i32,
+}
+
+fn default_dictionaries() -> Vec<YomitanSource> {
+    vec![]
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Yomitan {
+    #[serde(default = "default_dictionaries")]
+    pub dictionaries: Vec<YomitanSource>,
+}
+
 struct LocalKeycode;
 
 impl<'de> DeserializeAs<'de, Keycode> for LocalKeycode {
@@ -202,6 +732,42 @@ fn default_next_morpheme() -> Vec<Keycode> {
     vec![Keycode::LControl]
 }
 
+fn default_next_alternative() -> Vec<Keycode> {
+    vec![Keycode::RAlt]
+}
+
+fn default_next_senses() -> Vec<Keycode> {
+    vec![Keycode::F4]
+}
+
+fn default_next_kanji() -> Vec<Keycode> {
+    vec![Keycode::F3]
+}
+
+fn default_export() -> Vec<Keycode> {
+    vec![Keycode::F9]
+}
+
+fn default_detect_region() -> Vec<Keycode> {
+    vec![Keycode::F8]
+}
+
+fn default_hover_toggle() -> Vec<Keycode> {
+    vec![Keycode::F7]
+}
+
+fn default_hover_modifier() -> Vec<Keycode> {
+    vec![]
+}
+
+fn default_pin_toggle() -> Vec<Keycode> {
+    vec![Keycode::F6]
+}
+
+fn default_anchor_toggle() -> Vec<Keycode> {
+    vec![Keycode::F5]
+}
+
 #[serde_as]
 #[derive(Deserialize, Debug)]
 pub struct Keys {
@@ -223,6 +789,33 @@ pub struct Keys {
     #[serde_as(as = "Vec<LocalKeycode>")]
     #[serde(default = "default_next_morpheme")]
     pub next_morpheme: Vec<Keycode>,
+    #[serde_as(as = "Vec<LocalKeycode>")]
+    #[serde(default = "default_next_alternative")]
+    pub next_alternative: Vec<Keycode>,
+    #[serde_as(as = "Vec<LocalKeycode>")]
+    #[serde(default = "default_next_senses")]
+    pub next_senses: Vec<Keycode>,
+    #[serde_as(as = "Vec<LocalKeycode>")]
+    #[serde(default = "default_next_kanji")]
+    pub next_kanji: Vec<Keycode>,
+    #[serde_as(as = "Vec<LocalKeycode>")]
+    #[serde(default = "default_export")]
+    pub export: Vec<Keycode>,
+    #[serde_as(as = "Vec<LocalKeycode>")]
+    #[serde(default = "default_detect_region")]
+    pub detect_region: Vec<Keycode>,
+    #[serde_as(as = "Vec<LocalKeycode>")]
+    #[serde(default = "default_hover_toggle")]
+    pub hover_toggle: Vec<Keycode>,
+    #[serde_as(as = "Vec<LocalKeycode>")]
+    #[serde(default = "default_hover_modifier")]
+    pub hover_modifier: Vec<Keycode>,
+    #[serde_as(as = "Vec<LocalKeycode>")]
+    #[serde(default = "default_pin_toggle")]
+    pub pin_toggle: Vec<Keycode>,
+    #[serde_as(as = "Vec<LocalKeycode>")]
+    #[serde(default = "default_anchor_toggle")]
+    pub anchor_toggle: Vec<Keycode>,
 }
 
 impl Default for Keys {
@@ -234,6 +827,15 @@ impl Default for Keys {
             font_down: default_font_up(),
             next_hint: default_next_hint(),
             next_morpheme: default_next_morpheme(),
+            next_alternative: default_next_alternative(),
+            next_senses: default_next_senses(),
+            next_kanji: default_next_kanji(),
+            export: default_export(),
+            detect_region: default_detect_region(),
+            hover_toggle: default_hover_toggle(),
+            hover_modifier: default_hover_modifier(),
+            pin_toggle: default_pin_toggle(),
+            anchor_toggle: default_anchor_toggle(),
         }
     }
 }
@@ -0,0 +1,200 @@
+use std::cmp::Reverse;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use morph::Morpheme;
+
+use super::jmnedict::Jmnedict;
+use super::pos::is_proper_noun;
+use super::yomitan::YomitanDictionary;
+use super::{Dictionary, Lookup, LookupKey, LookupParams};
+use crate::config::{KSConfig, YomitanSource};
+
+/// the senses of a morpheme's best entry in one of the dictionaries
+#[derive(Debug, Clone)]
+pub struct Glosses {
+    /// the dictionary the entry comes from
+    pub dictionary: String,
+    /// which of the morpheme's forms found the entry
+    pub key: LookupKey,
+    /// the looked up form
+    pub headword: String,
+    /// the glosses of each sense
+    pub senses: Vec<Vec<String>>,
+}
+
+/// the senses of the JMdict entry found for one of the morpheme's forms
+pub fn jmdict_glosses(morpheme: &Morpheme, params: &LookupParams) -> Option<Glosses> {
+    glosses_of(Dictionary::get().lookup_morpheme(morpheme)?, params)
+}
+
+/// the senses of the JMdict entry found for the longest prefix of the morpheme having one
+pub fn jmdict_prefix_glosses(morpheme: &Morpheme, params: &LookupParams) -> Option<Glosses> {
+    glosses_of(Dictionary::get().lookup_prefixes(morpheme)?, params)
+}
+
+fn glosses_of(lookup: Lookup, params: &LookupParams) -> Option<Glosses> {
+    let senses = lookup
+        .matches
+        .first()?
+        .glosses(params)
+        .into_iter()
+        .map(|glosses| glosses.into_iter().map(str::to_owned).collect())
+        .collect();
+    Some(Glosses {
+        dictionary: "JMdict".to_owned(),
+        key: lookup.key,
+        headword: lookup.text,
+        senses,
+    })
+}
+
+/// the names a morpheme may be, one sense per JMnedict entry: its readings and kinds of name, then its
+/// translations
+pub fn jmnedict_glosses(names: &Jmnedict, morpheme: &Morpheme) -> Option<Glosses> {
+    let lookup = names.lookup_morpheme(morpheme)?;
+    let senses = lookup
+        .entries
+        .iter()
+        .map(|entry| {
+            let mut name_types: Vec<&str> = vec![];
+            for name_type in entry.translations.iter().flat_map(|t| &t.name_types) {
+                if !name_types.contains(&name_type.as_str()) {
+                    name_types.push(name_type);
+                }
+            }
+            let mut sense = vec![format!(
+                "{} [{}]",
+                entry.readings.join("、"),
+                name_types.join(", ")
+            )];
+            sense.extend(
+                entry
+                    .translations
+                    .iter()
+                    .flat_map(|t| t.details.iter().cloned()),
+            );
+            sense
+        })
+        .collect();
+    Some(Glosses {
+        dictionary: "JMnedict".to_owned(),
+        key: lookup.key,
+        headword: lookup.text,
+        senses,
+    })
+}
+
+/// the definitions of a morpheme's terms in a Yomitan dictionary, one sense per term
+pub fn yomitan_glosses(dictionary: &YomitanDictionary, morpheme: &Morpheme) -> Option<Glosses> {
+    let lookup = dictionary.lookup_morpheme(morpheme)?;
+    let senses = lookup
+        .terms
+        .iter()
+        .filter(|term| !term.glossary.is_empty())
+        .map(|term| term.glossary.clone())
+        .collect();
+    Some(Glosses {
+        dictionary: dictionary.title.clone(),
+        key: lookup.key,
+        headword: lookup.text,
+        senses,
+    })
+}
+
+/// JMnedict, unless it has no path or fails to load
+pub fn load_jmnedict(path: Option<PathBuf>) -> Option<Jmnedict> {
+    let path = path?;
+    match Jmnedict::load(&path) {
+        Ok(jmnedict) => Some(jmnedict),
+        Err(e) => {
+            warn!("Failed to load JMnedict from {:?}: {:?}", path, e);
+            None
+        }
+    }
+}
+
+/// the enabled Yomitan dictionaries, highest priority first
+pub fn load_yomitan(sources: &[YomitanSource]) -> Vec<YomitanDictionary> {
+    let mut dictionaries: Vec<YomitanDictionary> = sources
+        .iter()
+        .filter(|source| source.enabled && !source.path.is_empty())
+        .filter_map(|source| {
+            match YomitanDictionary::load(Path::new(&source.path), source.priority) {
+                Ok(dictionary) => Some(dictionary),
+                Err(e) => {
+                    warn!(
+                        "Failed to load Yomitan dictionary {:?}: {:?}",
+                        source.path, e
+                    );
+                    None
+                }
+            }
+        })
+        .collect();
+    dictionaries.sort_by_key(|dictionary| Reverse(dictionary.priority));
+    dictionaries
+}
+
+/// the dictionaries consulted along with JMdict, which is always there
+#[derive(Default)]
+pub struct Dictionaries {
+    pub jmnedict: Option<Jmnedict>,
+    /// highest priority first
+    pub yomitan: Vec<YomitanDictionary>,
+}
+
+impl Dictionaries {
+    pub fn load(config: &KSConfig) -> Dictionaries {
+        Dictionaries {
+            jmnedict: load_jmnedict(config.names.jmnedict()),
+            yomitan: load_yomitan(&config.yomitan.dictionaries),
+        }
+    }
+
+    /// the senses of a morpheme's best entry: the Yomitan dictionaries are consulted along with JMdict, by
+    /// priority, proper nouns are looked up in JMnedict first, other morphemes only when no other
+    /// dictionary has them; prefixes of the morpheme are only looked up once all of its forms failed
+    pub fn glosses(&self, morpheme: &Morpheme, params: &LookupParams) -> Option<Glosses> {
+        let (before, after): (Vec<_>, Vec<_>) = self
+            .yomitan
+            .iter()
+            .partition(|dictionary| dictionary.priority > 0);
+        let words = || {
+            before
+                .iter()
+                .map(|dictionary| yomitan_glosses(dictionary, morpheme))
+                .chain(std::iter::once_with(|| jmdict_glosses(morpheme, params)))
+                .chain(
+                    after
+                        .iter()
+                        .map(|dictionary| yomitan_glosses(dictionary, morpheme)),
+                )
+                .flatten()
+                .find(|glosses| !glosses.senses.is_empty())
+        };
+        let names = || {
+            self.jmnedict
+                .as_ref()
+                .and_then(|jmnedict| jmnedict_glosses(jmnedict, morpheme))
+        };
+        let prefixes = || jmdict_prefix_glosses(morpheme, params);
+        if is_proper_noun(&morpheme.part_of_speech) {
+            names().or_else(words)
+        } else {
+            words().or_else(names)
+        }
+        .or_else(prefixes)
+        .filter(|glosses| !glosses.senses.is_empty())
+    }
+
+    /// the frequencies of a morpheme, after the titles of the Yomitan dictionaries having them
+    pub fn frequencies(&self, morpheme: &Morpheme) -> Vec<(&str, &str)> {
+        self.yomitan
+            .iter()
+            .filter_map(|dictionary| {
+                Some((dictionary.title.as_str(), dictionary.frequency(morpheme)?))
+            })
+            .collect()
+    }
+}
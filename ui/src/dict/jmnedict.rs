@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Instant;
+
+use anyhow::Result;
+use log::debug;
+use morph::Morpheme;
+use roxmltree::{Document, Node, ParsingOptions};
+
+use super::LookupKey;
+use crate::script::to_hiragana;
+
+/// a translation of a name, e.g. `Yamada` as a surname
+#[derive(Debug, Clone, Default)]
+pub struct NameTranslation {
+    /// the kinds of name, e.g. "family or surname", "place name"
+    pub name_types: Vec<String>,
+    pub details: Vec<String>,
+}
+
+/// a JMnedict entry
+#[derive(Debug, Clone, Default)]
+pub struct NameEntry {
+    pub kanji: Vec<String>,
+    pub readings: Vec<String>,
+    pub translations: Vec<NameTranslation>,
+}
+
+fn texts(node: &Node, tag: &str) -> Vec<String> {
+    node.descendants()
+        .filter(|n| n.has_tag_name(tag))
+        .filter_map(|n| n.text())
+        .map(str::to_owned)
+        .collect()
+}
+
+impl NameEntry {
+    fn from_node(entry: &Node) -> NameEntry {
+        NameEntry {
+            kanji: texts(entry, "keb"),
+            readings: texts(entry, "reb"),
+            translations: entry
+                .children()
+                .filter(|n| n.has_tag_name("trans"))
+                .map(|trans| NameTranslation {
+                    name_types: texts(&trans, "name_type"),
+                    details: texts(&trans, "trans_det"),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// the names found for a morpheme, and how
+#[derive(Debug, Clone)]
+pub struct NameLookup<'a> {
+    pub key: LookupKey,
+    /// the looked up text
+    pub text: String,
+    pub entries: Vec<&'a NameEntry>,
+}
+
+/// JMnedict, the dictionary of Japanese proper names, from its XML file:
+/// <http://www.edrdg.org/enamdict/enamdict_doc.html>
+pub struct Jmnedict {
+    entries: Vec<NameEntry>,
+    /// entries by kanji forms and readings
+    by_form: HashMap<String, Vec<usize>>,
+}
+
+impl Jmnedict {
+    pub fn load(path: &Path) -> Result<Jmnedict> {
+        let start = Instant::now();
+        let xml = std::fs::read_to_string(path)?;
+        // the file starts with its DTD, which declares the entities of the name types
+        let options = ParsingOptions { allow_dtd: true };
+        let doc = Document::parse_with_options(&xml, options)?;
+        let entries: Vec<NameEntry> = doc
+            .root_element()
+            .children()
+            .filter(|n| n.has_tag_name("entry"))
+            .map(|n| NameEntry::from_node(&n))
+            .collect();
+        let mut by_form: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, entry) in entries.iter().enumerate() {
+            for form in entry.kanji.iter().chain(entry.readings.iter()) {
+                by_form.entry(form.clone()).or_default().push(i);
+            }
+        }
+        debug!(
+            "JMnedict loaded in {:?}: {} entries",
+            start.elapsed(),
+            entries.len()
+        );
+        Ok(Jmnedict { entries, by_form })
+    }
+
+    /// the entries with `text` as a kanji form or as a reading, in JMnedict's order
+    pub fn lookup(&self, text: &str) -> Vec<&NameEntry> {
+        self.by_form
+            .get(text)
+            .into_iter()
+            .flatten()
+            .map(|&i| &self.entries[i])
+            .collect()
+    }
+
+    /// the names a morpheme may be, looked up by its surface form, its lemma, then its reading; names
+    /// don't inflect, and UniDic often gives them a katakana lemma
+    pub fn lookup_morpheme(&self, morpheme: &Morpheme) -> Option<NameLookup<'_>> {
+        let lemma = morpheme.lemma.split('-').next().unwrap_or_default();
+        let reading = to_hiragana(&morpheme.pronounciation);
+        let candidates = [
+            (LookupKey::Surface, morpheme.text.as_str()),
+            (LookupKey::Lemma, lemma),
+            (LookupKey::Reading, reading.as_str()),
+        ];
+        for (key, text) in candidates {
+            if text.is_empty() || text == "*" {
+                continue;
+            }
+            let entries = self.lookup(text);
+            if !entries.is_empty() {
+                return Some(NameLookup {
+                    key,
+                    text: text.to_owned(),
+                    entries,
+                });
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Instant;
+
+use anyhow::Result;
+use log::debug;
+use roxmltree::{Document, Node, ParsingOptions};
+
+/// what KANJIDIC2 tells of a kanji
+#[derive(Debug, Clone, Default)]
+pub struct KanjiInfo {
+    pub literal: char,
+    /// English meanings
+    pub meanings: Vec<String>,
+    pub on_readings: Vec<String>,
+    pub kun_readings: Vec<String>,
+    pub stroke_count: Option<u32>,
+    /// school grade: 1 to 6 for the kyōiku kanji, 8 for the other jōyō kanji, 9 and 10 for jinmeiyō kanji
+    pub grade: Option<u32>,
+    /// level of the former JLPT, 4 being the easiest
+    pub jlpt: Option<u32>,
+    /// rank among the 2500 most used kanji in newspapers
+    pub frequency: Option<u32>,
+}
+
+fn child_text<'a>(node: &Node<'a, '_>, tag: &str) -> Option<&'a str> {
+    node.children()
+        .find(|n| n.has_tag_name(tag))
+        .and_then(|n| n.text())
+}
+
+impl KanjiInfo {
+    fn from_node(character: &Node) -> Option<KanjiInfo> {
+        let literal = child_text(character, "literal")?.chars().next()?;
+        let misc = character.children().find(|n| n.has_tag_name("misc"));
+        let misc_number = |tag| {
+            misc.as_ref()
+                .and_then(|misc| child_text(misc, tag))
+                .and_then(|text| text.parse().ok())
+        };
+
+        let mut info = KanjiInfo {
+            literal,
+            stroke_count: misc_number("stroke_count"),
+            grade: misc_number("grade"),
+            jlpt: misc_number("jlpt"),
+            frequency: misc_number("freq"),
+            ..Default::default()
+        };
+        let rmgroups = character
+            .descendants()
+            .filter(|n| n.has_tag_name("rmgroup"));
+        for n in rmgroups.flat_map(|rmgroup| rmgroup.children()) {
+            let text = match n.text() {
+                Some(text) => text.to_owned(),
+                None => continue,
+            };
+            if n.has_tag_name("reading") {
+                match n.attribute("r_type") {
+                    Some("ja_on") => info.on_readings.push(text),
+                    Some("ja_kun") => info.kun_readings.push(text),
+                    _ => {}
+                }
+            } else if n.has_tag_name("meaning") && n.attribute("m_lang").is_none() {
+                info.meanings.push(text);
+            }
+        }
+        Some(info)
+    }
+}
+
+/// KANJIDIC2, from its XML file: <http://www.edrdg.org/wiki/index.php/KANJIDIC_Project>
+pub struct Kanjidic {
+    kanji: HashMap<char, KanjiInfo>,
+}
+
+impl Kanjidic {
+    pub fn load(path: &Path) -> Result<Kanjidic> {
+        let start = Instant::now();
+        let xml = std::fs::read_to_string(path)?;
+        // the file starts with its DTD
+        let options = ParsingOptions { allow_dtd: true };
+        let doc = Document::parse_with_options(&xml, options)?;
+        let kanji: HashMap<char, KanjiInfo> = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("character"))
+            .filter_map(|n| KanjiInfo::from_node(&n))
+            .map(|info| (info.literal, info))
+            .collect();
+        debug!(
+            "KANJIDIC2 loaded in {:?}: {} kanji",
+            start.elapsed(),
+            kanji.len()
+        );
+        Ok(Kanjidic { kanji })
+    }
+
+    pub fn get(&self, literal: char) -> Option<&KanjiInfo> {
+        self.kanji.get(&literal)
+    }
+}
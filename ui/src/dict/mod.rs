@@ -0,0 +1,377 @@
+pub mod glosses;
+pub mod jmnedict;
+pub mod kanjidic;
+pub mod pos;
+pub mod radicals;
+pub mod yomitan;
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Instant;
+
+use jmdict::{Entry, Enum, GlossLanguage, Priority, PriorityInCorpus, Sense};
+use log::debug;
+use morph::Morpheme;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::script::to_hiragana;
+
+/// which of an entry's forms matched the looked up text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MatchKind {
+    Kanji,
+    Reading,
+}
+
+/// what of an entry's senses is shown
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LookupParams {
+    /// senses marked obsolete or archaic
+    pub obsolete_senses: bool,
+    /// the languages of the glosses, as JMdict's ISO 639-2 codes, e.g. `eng`, `ger`, `fre`; the others
+    /// are only there if built with their `translations-*` feature
+    pub languages: Vec<String>,
+}
+
+impl LookupParams {
+    /// the chosen languages the jmdict crate was built without, whose glosses are thus missing
+    pub fn missing_languages(&self) -> Vec<&str> {
+        self.languages
+            .iter()
+            .map(String::as_str)
+            .filter(|&code| GlossLanguage::from_code(code).is_none())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Match {
+    pub entry: Entry,
+    pub kind: MatchKind,
+    /// the priority of the matched form
+    pub priority: Priority,
+    /// whether one of the entry's senses has the looked up part of speech
+    pub pos_match: bool,
+}
+
+impl Match {
+    /// the entry's senses, obsolete and archaic ones left out unless asked for or unless there are no others
+    pub fn senses(&self, params: &LookupParams) -> Vec<Sense> {
+        let current: Vec<Sense> = self
+            .entry
+            .senses()
+            .filter(|sense| params.obsolete_senses || !is_obsolete(sense))
+            .collect();
+        if current.is_empty() {
+            self.entry.senses().collect()
+        } else {
+            current
+        }
+    }
+
+    /// the glosses of the entry's senses in the chosen languages, each sense translated in none of them
+    /// being shown in English; senses in none of them nor in English are left out
+    pub fn glosses(&self, params: &LookupParams) -> Vec<Vec<&'static str>> {
+        let languages: Vec<&str> = params.languages.iter().map(String::as_str).collect();
+        self.senses(params)
+            .iter()
+            .map(|sense| {
+                let in_languages = |languages: &[&str]| -> Vec<&'static str> {
+                    sense
+                        .glosses()
+                        .filter(|gloss| languages.contains(&gloss.language.code()))
+                        .map(|gloss| gloss.text)
+                        .collect()
+                };
+                let glosses = in_languages(&languages);
+                if glosses.is_empty() {
+                    in_languages(&["eng"])
+                } else {
+                    glosses
+                }
+            })
+            .filter(|glosses| !glosses.is_empty())
+            .collect()
+    }
+}
+
+fn is_obsolete(sense: &Sense) -> bool {
+    sense
+        .infos()
+        .any(|info| matches!(info.code(), "obs" | "arch"))
+}
+
+/// how common a form is: whether JMdict deems it so, then its presence in the news1/2, ichi1/2, gai1/2 and
+/// spec1/2 lists, then its word frequency rank, if any
+fn priority_score(priority: &Priority) -> u32 {
+    let listed = |in_corpus: PriorityInCorpus| match in_corpus {
+        PriorityInCorpus::Primary => 2,
+        PriorityInCorpus::Secondary => 1,
+        PriorityInCorpus::Absent => 0,
+    };
+    // 48 buckets of 500 words, the first being the most frequent
+    let frequency = match priority.frequency_bucket {
+        0 => 0,
+        bucket => 49u32.saturating_sub(bucket as u32),
+    };
+    priority.is_common() as u32 * 1000
+        + (listed(priority.news)
+            + listed(priority.ichimango)
+            + listed(priority.loanwords)
+            + listed(priority.additional))
+            * 100
+        + frequency
+}
+
+/// which of a morpheme's forms found its entries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LookupKey {
+    Lemma,
+    BaseForm,
+    Surface,
+    Reading,
+    Prefix,
+}
+
+impl fmt::Display for LookupKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            LookupKey::Lemma => "dictionary form",
+            LookupKey::BaseForm => "base form",
+            LookupKey::Surface => "surface form",
+            LookupKey::Reading => "reading",
+            LookupKey::Prefix => "prefix",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// the entries found for a morpheme, and how
+#[derive(Debug, Clone)]
+pub struct Lookup {
+    pub key: LookupKey,
+    /// the looked up text
+    pub text: String,
+    pub matches: Vec<Match>,
+}
+
+/// JMdict, indexed by kanji forms and readings
+pub struct Dictionary {
+    entries: Vec<Entry>,
+    by_kanji: HashMap<&'static str, Vec<usize>>,
+    by_reading: HashMap<&'static str, Vec<usize>>,
+}
+
+static DICTIONARY: Lazy<Dictionary> = Lazy::new(|| {
+    let start = Instant::now();
+    let dictionary = Dictionary::build();
+    debug!(
+        "JMdict indexed in {:?}: {} entries",
+        start.elapsed(),
+        dictionary.entries.len()
+    );
+    dictionary
+});
+
+/// the forms a morpheme is looked up by, in order, and whether they may match kanji forms: its lemma, its base
+/// form and its surface form, then its reading in hiragana among readings only; UniDic's placeholders left out
+pub(crate) fn lookup_candidates(morpheme: &Morpheme) -> Vec<(LookupKey, String, bool)> {
+    // UniDic lemmas may carry a gloss or a disambiguation, e.g. ニュース-news or 君-代名詞
+    let lemma = morpheme.lemma.split('-').next().unwrap_or_default();
+    [
+        (LookupKey::Lemma, lemma.to_owned(), true),
+        (LookupKey::BaseForm, morpheme.orth_base.clone(), true),
+        (LookupKey::Surface, morpheme.text.clone(), true),
+        (
+            LookupKey::Reading,
+            to_hiragana(&morpheme.pronounciation),
+            false,
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, text, _)| !text.is_empty() && text != "*")
+    .collect()
+}
+
+impl Dictionary {
+    fn build() -> Dictionary {
+        let entries: Vec<Entry> = jmdict::entries().collect();
+        let mut by_kanji: HashMap<&'static str, Vec<usize>> = HashMap::new();
+        let mut by_reading: HashMap<&'static str, Vec<usize>> = HashMap::new();
+        for (i, entry) in entries.iter().enumerate() {
+            for k in entry.kanji_elements() {
+                by_kanji.entry(k.text).or_default().push(i);
+            }
+            for r in entry.reading_elements() {
+                by_reading.entry(r.text).or_default().push(i);
+            }
+        }
+        Dictionary {
+            entries,
+            by_kanji,
+            by_reading,
+        }
+    }
+
+    /// the dictionary, indexed on first use; a few hundred milliseconds, better spent ahead on another thread
+    pub fn get() -> &'static Dictionary {
+        &DICTIONARY
+    }
+
+    /// the entries with `text` as a kanji form or as a reading, best matches first: those with the
+    /// morpheme's UniDic part of speech, if given, then the most common, then kanji forms over readings,
+    /// otherwise JMdict's order is kept
+    pub fn lookup(&self, text: &str, part_of_speech: Option<&str>) -> Vec<Match> {
+        self.lookup_forms(text, part_of_speech, true)
+    }
+
+    /// the entries with `text` as a reading, ranked as by `lookup`
+    pub fn lookup_reading(&self, text: &str, part_of_speech: Option<&str>) -> Vec<Match> {
+        self.lookup_forms(text, part_of_speech, false)
+    }
+
+    /// the entries of a morpheme, looked up by its lemma, its base form and its surface form, then by its
+    /// reading among readings only; the first key with entries wins
+    pub fn lookup_morpheme(&self, morpheme: &Morpheme) -> Option<Lookup> {
+        let part_of_speech = Some(morpheme.part_of_speech.as_str());
+        lookup_candidates(morpheme)
+            .into_iter()
+            .find_map(|(key, text, kanji_forms)| {
+                let matches = self.lookup_forms(&text, part_of_speech, kanji_forms);
+                (!matches.is_empty()).then_some(Lookup { key, text, matches })
+            })
+    }
+
+    /// the entries of the longest prefix of a morpheme's surface form having some, a last resort once all
+    /// of its forms failed
+    pub fn lookup_prefixes(&self, morpheme: &Morpheme) -> Option<Lookup> {
+        // the morpheme's part of speech says nothing of its prefixes
+        let ends: Vec<usize> = morpheme
+            .text
+            .char_indices()
+            .map(|(i, _)| i)
+            .skip(1)
+            .collect();
+        ends.into_iter().rev().find_map(|end| {
+            let prefix = &morpheme.text[..end];
+            let matches = self.lookup(prefix, None);
+            (!matches.is_empty()).then(|| Lookup {
+                key: LookupKey::Prefix,
+                text: prefix.to_owned(),
+                matches,
+            })
+        })
+    }
+
+    fn lookup_forms(
+        &self,
+        text: &str,
+        part_of_speech: Option<&str>,
+        kanji_forms: bool,
+    ) -> Vec<Match> {
+        let kanji_indices = if kanji_forms {
+            self.by_kanji.get(text)
+        } else {
+            None
+        };
+        let kanji = kanji_indices.into_iter().flatten().map(|&i| {
+            let entry = self.entries[i];
+            let priority = entry
+                .kanji_elements()
+                .find(|k| k.text == text)
+                .map(|k| k.priority);
+            (i, MatchKind::Kanji, priority)
+        });
+        let reading = self.by_reading.get(text).into_iter().flatten().map(|&i| {
+            let entry = self.entries[i];
+            let priority = entry
+                .reading_elements()
+                .find(|r| r.text == text)
+                .map(|r| r.priority);
+            (i, MatchKind::Reading, priority)
+        });
+
+        let mut seen = vec![];
+        let mut matches: Vec<Match> = kanji
+            .chain(reading)
+            .filter(|(i, _, _)| {
+                if seen.contains(i) {
+                    false
+                } else {
+                    seen.push(*i);
+                    true
+                }
+            })
+            .map(|(i, kind, priority)| {
+                let entry = self.entries[i];
+                let pos_match = part_of_speech.map_or(false, |pos| {
+                    entry.senses().any(|sense| {
+                        sense
+                            .parts_of_speech()
+                            .any(|jmdict_pos| pos::matches(pos, jmdict_pos.code()))
+                    })
+                });
+                Match {
+                    entry,
+                    kind,
+                    priority: priority.unwrap_or_default(),
+                    pos_match,
+                }
+            })
+            .collect();
+        matches.sort_by_key(|m| {
+            (
+                Reverse(m.pos_match),
+                Reverse(priority_score(&m.priority)),
+                m.kind,
+            )
+        });
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn morpheme(text: &str, lemma: &str, orth_base: &str, pronounciation: &str) -> Morpheme {
+        Morpheme {
+            text: text.to_owned(),
+            lemma: lemma.to_owned(),
+            orth_base: orth_base.to_owned(),
+            pronounciation: pronounciation.to_owned(),
+            part_of_speech: "動詞-一般".to_owned(),
+            inflection_type: None,
+            inflection_form: None,
+        }
+    }
+
+    #[test]
+    fn looks_up_lemma_base_form_surface_then_reading() {
+        let candidates = lookup_candidates(&morpheme("食べ", "食べる", "食べる", "タベ"));
+        assert_eq!(
+            candidates,
+            [
+                (LookupKey::Lemma, "食べる".to_owned(), true),
+                (LookupKey::BaseForm, "食べる".to_owned(), true),
+                (LookupKey::Surface, "食べ".to_owned(), true),
+                (LookupKey::Reading, "たべ".to_owned(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_out_lemma_glosses_and_placeholders() {
+        let candidates = lookup_candidates(&morpheme("ニュース", "ニュース-news", "*", ""));
+        assert_eq!(
+            candidates,
+            [
+                (LookupKey::Lemma, "ニュース".to_owned(), true),
+                (LookupKey::Surface, "ニュース".to_owned(), true),
+            ]
+        );
+    }
+}
@@ -0,0 +1,80 @@
+/// JMdict part of speech codes (prefixes of) for a UniDic part of speech, e.g. `名詞-普通名詞-サ変可能`
+///
+/// see <https://www.edrdg.org/jmdictdb/cgi-bin/edhelp.py?svc=jmdict&sid=#kw_pos> for JMdict's codes
+fn jmdict_codes(unidic_pos: &str) -> &'static [&'static str] {
+    let mut levels = unidic_pos.split('-');
+    let major = levels.next().unwrap_or_default();
+    let minor = levels.next().unwrap_or_default();
+    let small = levels.next().unwrap_or_default();
+    match (major, minor, small) {
+        ("名詞", "普通名詞", "サ変可能") => &["n", "vs"],
+        ("名詞", "普通名詞", "形状詞可能") => &["n", "adj-na"],
+        ("名詞", "普通名詞", "副詞可能") => &["n", "adv"],
+        ("名詞", "普通名詞", "助数詞可能") => &["n", "ctr"],
+        ("名詞", "固有名詞", _) => &["n"],
+        ("名詞", "数詞", _) => &["num", "n"],
+        ("名詞", "助動詞語幹", _) => &["aux"],
+        ("名詞", _, _) => &["n"],
+        ("代名詞", _, _) => &["pn", "n"],
+        ("動詞", "非自立可能", _) => &["v", "aux-v"],
+        ("動詞", _, _) => &["v"],
+        ("形容詞", "非自立可能", _) => &["adj-i", "aux-adj"],
+        ("形容詞", _, _) => &["adj-i"],
+        ("形状詞", "タリ", _) => &["adj-t"],
+        ("形状詞", "助動詞語幹", _) => &["aux"],
+        ("形状詞", _, _) => &["adj-na"],
+        ("連体詞", _, _) => &["adj-pn", "adj-f"],
+        ("副詞", _, _) => &["adv"],
+        ("接続詞", _, _) => &["conj"],
+        ("感動詞", _, _) => &["int"],
+        ("助詞", _, _) => &["prt"],
+        ("助動詞", _, _) => &["aux"],
+        ("接頭辞", _, _) => &["pref"],
+        ("接尾辞", _, _) => &["suf", "n-suf", "ctr"],
+        _ => &[],
+    }
+}
+
+/// whether the UniDic part of speech is that of a proper noun, e.g. `名詞-固有名詞-人名-姓`
+pub fn is_proper_noun(unidic_pos: &str) -> bool {
+    unidic_pos.starts_with("名詞-固有名詞")
+}
+
+/// whether the JMdict part of speech code fits the UniDic part of speech
+pub fn matches(unidic_pos: &str, jmdict_code: &str) -> bool {
+    jmdict_codes(unidic_pos)
+        .iter()
+        .any(|prefix| jmdict_code.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_unidic_to_jmdict() {
+        assert_eq!(jmdict_codes("名詞-普通名詞-サ変可能"), ["n", "vs"]);
+        assert_eq!(jmdict_codes("名詞-普通名詞-一般"), ["n"]);
+        assert_eq!(jmdict_codes("名詞-固有名詞-人名-姓"), ["n"]);
+        assert_eq!(jmdict_codes("動詞-一般"), ["v"]);
+        assert_eq!(jmdict_codes("形状詞-タリ"), ["adj-t"]);
+        assert_eq!(jmdict_codes("助詞-格助詞"), ["prt"]);
+        assert!(jmdict_codes("補助記号-句点").is_empty());
+        assert!(jmdict_codes("").is_empty());
+    }
+
+    #[test]
+    fn matches_code_prefixes() {
+        assert!(matches("動詞-一般", "v1"));
+        assert!(matches("動詞-一般", "v5r"));
+        assert!(matches("名詞-普通名詞-サ変可能", "vs"));
+        assert!(!matches("名詞-普通名詞-一般", "vs"));
+        assert!(!matches("形容詞-一般", "adj-na"));
+    }
+
+    #[test]
+    fn tells_proper_nouns() {
+        assert!(is_proper_noun("名詞-固有名詞-地名-一般"));
+        assert!(!is_proper_noun("名詞-普通名詞-一般"));
+    }
+}
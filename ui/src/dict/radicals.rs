@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Result;
+use encoding_rs::EUC_JP;
+use log::warn;
+
+/// the radicals kanji are made of, from KRADFILE and RADKFILE: <http://www.edrdg.org/krad/kradinf.html>
+#[derive(Default)]
+pub struct Radicals {
+    /// the radicals of each kanji, from KRADFILE
+    components: HashMap<char, Vec<char>>,
+    /// the stroke count of each radical and the kanji having it, from RADKFILE
+    radicals: HashMap<char, (u32, Vec<char>)>,
+}
+
+/// the files are encoded in EUC-JP
+fn read_euc_jp(path: &Path) -> Result<String> {
+    let bytes = std::fs::read(path)?;
+    let (text, _, had_errors) = EUC_JP.decode(&bytes);
+    if had_errors {
+        warn!("Invalid EUC-JP sequences in {:?}", path);
+    }
+    Ok(text.into_owned())
+}
+
+impl Radicals {
+    pub fn load(kradfile: Option<&Path>, radkfile: Option<&Path>) -> Result<Radicals> {
+        let mut radicals = Radicals::default();
+        if let Some(path) = kradfile {
+            radicals.parse_kradfile(&read_euc_jp(path)?);
+        }
+        if let Some(path) = radkfile {
+            radicals.parse_radkfile(&read_euc_jp(path)?);
+        }
+        Ok(radicals)
+    }
+
+    /// lines such as `語 : 言 五 口`
+    fn parse_kradfile(&mut self, text: &str) {
+        for line in text.lines().filter(|line| !line.starts_with('#')) {
+            if let Some((kanji, components)) = line.split_once(" : ") {
+                if let Some(kanji) = kanji.chars().next() {
+                    let components = components.split_whitespace().flat_map(str::chars);
+                    self.components.insert(kanji, components.collect());
+                }
+            }
+        }
+    }
+
+    /// a line such as `$ 言 7` for each radical and its stroke count, followed by lines of the kanji having it
+    fn parse_radkfile(&mut self, text: &str) {
+        let mut current = None;
+        for line in text.lines().filter(|line| !line.starts_with('#')) {
+            if let Some(header) = line.strip_prefix('$') {
+                let mut fields = header.split_whitespace();
+                let radical = fields.next().and_then(|r| r.chars().next());
+                let strokes = fields
+                    .next()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or_default();
+                current = radical;
+                if let Some(radical) = radical {
+                    self.radicals.insert(radical, (strokes, vec![]));
+                }
+            } else if let Some((_, kanji)) = current.and_then(|r| self.radicals.get_mut(&r)) {
+                kanji.extend(line.chars().filter(|c| !c.is_whitespace()));
+            }
+        }
+    }
+
+    /// the radicals of a kanji, fewest strokes first when RADKFILE tells
+    pub fn radicals(&self, kanji: char) -> Vec<char> {
+        let mut radicals = self.components.get(&kanji).cloned().unwrap_or_default();
+        radicals.sort_by_key(|r| self.strokes(*r).unwrap_or(u32::MAX));
+        radicals
+    }
+
+    pub fn strokes(&self, radical: char) -> Option<u32> {
+        self.radicals.get(&radical).map(|(strokes, _)| *strokes)
+    }
+
+    /// the kanji having a radical
+    pub fn kanji_with(&self, radical: char) -> &[char] {
+        self.radicals
+            .get(&radical)
+            .map(|(_, kanji)| kanji.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KRADFILE: &str = "# KRADFILE\n語 : 言 五 口\n休 : 化 木\n";
+    const RADKFILE: &str = "# RADKFILE\n$ 口 3\n語可\n古\n$ 五 4\n語\n$ 言 7 js01\n語\n";
+
+    #[test]
+    fn parses_kradfile() {
+        let mut radicals = Radicals::default();
+        radicals.parse_kradfile(KRADFILE);
+        assert_eq!(radicals.radicals('語'), ['言', '五', '口']);
+        assert_eq!(radicals.radicals('休'), ['化', '木']);
+        assert!(radicals.radicals('#').is_empty());
+    }
+
+    #[test]
+    fn parses_radkfile() {
+        let mut radicals = Radicals::default();
+        radicals.parse_radkfile(RADKFILE);
+        assert_eq!(radicals.strokes('口'), Some(3));
+        assert_eq!(radicals.strokes('言'), Some(7));
+        assert_eq!(radicals.kanji_with('口'), ['語', '可', '古']);
+        assert_eq!(radicals.kanji_with('言'), ['語']);
+        assert!(radicals.kanji_with('木').is_empty());
+    }
+
+    #[test]
+    fn sorts_radicals_by_strokes() {
+        let mut radicals = Radicals::default();
+        radicals.parse_kradfile(KRADFILE);
+        radicals.parse_radkfile(RADKFILE);
+        assert_eq!(radicals.radicals('語'), ['口', '五', '言']);
+    }
+}
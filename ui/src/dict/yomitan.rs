@@ -0,0 +1,400 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::time::Instant;
+
+use anyhow::Result;
+use log::debug;
+use morph::Morpheme;
+use serde_json::Value;
+use zip::ZipArchive;
+
+use super::kanjidic::KanjiInfo;
+use super::{lookup_candidates, LookupKey};
+use crate::script::to_hiragana;
+
+/// a row of a term bank: one sense, or all of them, depending on the dictionary
+#[derive(Debug, Clone, Default)]
+pub struct Term {
+    pub expression: String,
+    /// empty when the expression is written in kana
+    pub reading: String,
+    /// how the dictionary ranks the terms sharing an expression, the higher the better
+    pub score: i64,
+    pub glossary: Vec<String>,
+}
+
+/// an entry of a term meta bank, e.g. `{"reading": "ひと", "frequency": {"value": 42, "displayValue": "42㋕"}}`
+#[derive(Debug, Clone, Default)]
+pub struct Frequency {
+    /// the reading the frequency is for, if the dictionary tells
+    pub reading: Option<String>,
+    pub value: String,
+}
+
+/// the terms found for a morpheme, and how
+#[derive(Debug, Clone)]
+pub struct TermLookup<'a> {
+    pub key: LookupKey,
+    /// the looked up text
+    pub text: String,
+    pub terms: Vec<&'a Term>,
+}
+
+/// the text of structured content, images and markup left out
+fn content_text(content: &Value, text: &mut String) {
+    match content {
+        Value::String(s) => text.push_str(s),
+        Value::Array(items) => items.iter().for_each(|item| content_text(item, text)),
+        Value::Object(object) => {
+            if object.get("tag").and_then(Value::as_str) == Some("img") {
+                return;
+            }
+            if let Some(content) = object.get("content") {
+                content_text(content, text);
+            }
+            // keep the items of lists and the like apart
+            text.push(' ');
+        }
+        _ => {}
+    }
+}
+
+/// a definition of a glossary: a string, or an object of text or structured content; images and
+/// deinflections are left out
+fn definition_text(definition: &Value) -> Option<String> {
+    let text = match definition {
+        Value::String(s) => s.clone(),
+        Value::Object(object) => match object.get("type").and_then(Value::as_str)? {
+            "text" => object.get("text")?.as_str()?.to_owned(),
+            "structured-content" => {
+                let mut text = String::new();
+                content_text(object.get("content")?, &mut text);
+                text.split_whitespace().collect::<Vec<_>>().join(" ")
+            }
+            _ => return None,
+        },
+        _ => return None,
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+/// `[expression, reading, definition tags, rules, score, glossary, sequence, term tags]`; the first version
+/// of the format has the glossary as the rest of the row, and no sequence nor term tags
+fn parse_term(row: &Value) -> Option<Term> {
+    let row = row.as_array()?;
+    let glossary: Vec<&Value> = match row.get(5)? {
+        Value::Array(glossary) => glossary.iter().collect(),
+        _ => row.iter().skip(5).collect(),
+    };
+    Some(Term {
+        expression: row.first()?.as_str()?.to_owned(),
+        reading: row.get(1)?.as_str()?.to_owned(),
+        score: row.get(4).and_then(Value::as_i64).unwrap_or_default(),
+        glossary: glossary.into_iter().filter_map(definition_text).collect(),
+    })
+}
+
+/// `[character, onyomi, kunyomi, tags, meanings, stats]`; the first version of the format has the meanings
+/// as the rest of the row, and no stats
+fn parse_kanji(row: &Value) -> Option<KanjiInfo> {
+    let row = row.as_array()?;
+    let field = |i: usize| row.get(i).and_then(Value::as_str).unwrap_or_default();
+    let words = |s: &str| -> Vec<String> { s.split_whitespace().map(str::to_owned).collect() };
+    let meanings: Vec<&Value> = match row.get(4) {
+        Some(Value::Array(meanings)) => meanings.iter().collect(),
+        _ => row.iter().skip(4).collect(),
+    };
+    let stats = row.get(5).and_then(Value::as_object);
+    let stat = |key: &str| {
+        stats
+            .and_then(|stats| stats.get(key))
+            .and_then(Value::as_str)
+            .and_then(|value| value.parse().ok())
+    };
+    Some(KanjiInfo {
+        literal: field(0).chars().next()?,
+        meanings: meanings
+            .into_iter()
+            .filter_map(Value::as_str)
+            .map(str::to_owned)
+            .collect(),
+        on_readings: words(field(1)),
+        kun_readings: words(field(2)),
+        stroke_count: stat("strokes"),
+        grade: stat("grade"),
+        jlpt: stat("jlpt"),
+        frequency: stat("freq"),
+    })
+}
+
+/// a frequency as a number, a string, or an object with a value and maybe how to display it
+fn frequency_value(data: &Value) -> Option<String> {
+    match data {
+        Value::Number(n) => Some(n.to_string()),
+        Value::String(s) => Some(s.clone()),
+        Value::Object(object) => object
+            .get("displayValue")
+            .and_then(Value::as_str)
+            .map(str::to_owned)
+            .or_else(|| object.get("value").map(Value::to_string)),
+        _ => None,
+    }
+}
+
+/// `[expression, "freq", data]`, data possibly being for one of the expression's readings; pitch accents
+/// and the like are left out
+fn parse_frequency(row: &Value) -> Option<(String, Frequency)> {
+    let row = row.as_array()?;
+    if row.get(1)?.as_str()? != "freq" {
+        return None;
+    }
+    let data = row.get(2)?;
+    let frequency = match data.get("frequency") {
+        Some(frequency) => Frequency {
+            reading: data
+                .get("reading")
+                .and_then(Value::as_str)
+                .map(str::to_owned),
+            value: frequency_value(frequency)?,
+        },
+        None => Frequency {
+            reading: None,
+            value: frequency_value(data)?,
+        },
+    };
+    Some((row.first()?.as_str()?.to_owned(), frequency))
+}
+
+/// a Yomichan/Yomitan dictionary, from its zip archive: <https://github.com/themoeway/yomitan>
+pub struct YomitanDictionary {
+    pub title: String,
+    /// dictionaries with a positive priority are consulted before JMdict, the others after it but before
+    /// JMdict's prefix fallback, the highest first
+    pub priority: i32,
+    terms: Vec<Term>,
+    by_expression: HashMap<String, Vec<usize>>,
+    by_reading: HashMap<String, Vec<usize>>,
+    kanji: HashMap<char, KanjiInfo>,
+    frequencies: HashMap<String, Vec<Frequency>>,
+}
+
+impl YomitanDictionary {
+    pub fn load(path: &Path, priority: i32) -> Result<YomitanDictionary> {
+        let start = Instant::now();
+        let mut archive = ZipArchive::new(File::open(path)?)?;
+        let mut dictionary = YomitanDictionary {
+            title: path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            priority,
+            terms: vec![],
+            by_expression: HashMap::new(),
+            by_reading: HashMap::new(),
+            kanji: HashMap::new(),
+            frequencies: HashMap::new(),
+        };
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            let name = file.name().to_owned();
+            // the archive may also hold the images of structured content
+            if !name.ends_with(".json") {
+                continue;
+            }
+            let mut json = String::new();
+            file.read_to_string(&mut json)?;
+            if name == "index.json" {
+                let index: Value = serde_json::from_str(&json)?;
+                if let Some(title) = index.get("title").and_then(Value::as_str) {
+                    dictionary.title = title.to_owned();
+                }
+                continue;
+            }
+            let rows = || serde_json::from_str::<Vec<Value>>(&json);
+            if name.starts_with("term_bank_") {
+                dictionary
+                    .terms
+                    .extend(rows()?.iter().filter_map(parse_term));
+            } else if name.starts_with("kanji_bank_") {
+                let kanji = rows()?.iter().filter_map(parse_kanji).collect::<Vec<_>>();
+                dictionary
+                    .kanji
+                    .extend(kanji.into_iter().map(|info| (info.literal, info)));
+            } else if name.starts_with("term_meta_bank_") {
+                for (expression, frequency) in rows()?.iter().filter_map(parse_frequency) {
+                    dictionary
+                        .frequencies
+                        .entry(expression)
+                        .or_default()
+                        .push(frequency);
+                }
+            }
+        }
+        for (i, term) in dictionary.terms.iter().enumerate() {
+            dictionary
+                .by_expression
+                .entry(term.expression.clone())
+                .or_default()
+                .push(i);
+            let reading = if term.reading.is_empty() {
+                &term.expression
+            } else {
+                &term.reading
+            };
+            dictionary
+                .by_reading
+                .entry(reading.clone())
+                .or_default()
+                .push(i);
+        }
+        debug!(
+            "Yomitan dictionary {:?} loaded in {:?}: {} terms, {} kanji, {} frequencies",
+            dictionary.title,
+            start.elapsed(),
+            dictionary.terms.len(),
+            dictionary.kanji.len(),
+            dictionary.frequencies.len()
+        );
+        Ok(dictionary)
+    }
+
+    /// the terms with `text` as their expression or, unless `expressions` is false, as their reading,
+    /// best scored first
+    fn lookup_forms(&self, text: &str, expressions: bool) -> Vec<&Term> {
+        let by_expression = if expressions {
+            self.by_expression.get(text)
+        } else {
+            None
+        };
+        let mut indices: Vec<usize> = by_expression
+            .into_iter()
+            .chain(self.by_reading.get(text))
+            .flatten()
+            .copied()
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+        let mut terms: Vec<&Term> = indices.into_iter().map(|i| &self.terms[i]).collect();
+        terms.sort_by_key(|term| std::cmp::Reverse(term.score));
+        terms
+    }
+
+    /// the terms of a morpheme, looked up by its lemma, its base form and its surface form, then by its
+    /// reading among readings only; the first key with terms wins
+    pub fn lookup_morpheme(&self, morpheme: &Morpheme) -> Option<TermLookup<'_>> {
+        lookup_candidates(morpheme)
+            .into_iter()
+            .find_map(|(key, text, expressions)| {
+                let terms = self.lookup_forms(&text, expressions);
+                (!terms.is_empty()).then_some(TermLookup { key, text, terms })
+            })
+    }
+
+    pub fn kanji(&self, literal: char) -> Option<&KanjiInfo> {
+        self.kanji.get(&literal)
+    }
+
+    /// the frequency of a morpheme, by its lemma, its base form or its surface form, for its reading when
+    /// the dictionary tells the frequencies of each reading
+    pub fn frequency(&self, morpheme: &Morpheme) -> Option<&str> {
+        let lemma = morpheme.lemma.split('-').next().unwrap_or_default();
+        let reading = to_hiragana(&morpheme.pronounciation);
+        [lemma, morpheme.orth_base.as_str(), morpheme.text.as_str()]
+            .into_iter()
+            .filter_map(|form| self.frequencies.get(form))
+            .flatten()
+            .find(|frequency| frequency.reading.iter().all(|r| to_hiragana(r) == reading))
+            .map(|frequency| frequency.value.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn parses_terms() {
+        let row = json!([
+            "猫",
+            "ねこ",
+            "n",
+            "",
+            10,
+            [
+                "cat",
+                {"type": "text", "text": "feline"},
+                {"type": "image", "path": "cat.png"},
+                {
+                    "type": "structured-content",
+                    "content": {"tag": "ul", "content": [
+                        {"tag": "li", "content": "small"},
+                        {"tag": "li", "content": ["furry", {"tag": "img", "path": "x.png"}]}
+                    ]}
+                }
+            ],
+            1467640,
+            "P"
+        ]);
+        let term = parse_term(&row).unwrap();
+        assert_eq!(term.expression, "猫");
+        assert_eq!(term.reading, "ねこ");
+        assert_eq!(term.score, 10);
+        assert_eq!(term.glossary, ["cat", "feline", "small furry"]);
+    }
+
+    #[test]
+    fn parses_first_version_terms() {
+        let row = json!(["犬", "いぬ", "n", "", 0, "dog", "hound"]);
+        let term = parse_term(&row).unwrap();
+        assert_eq!(term.expression, "犬");
+        assert_eq!(term.glossary, ["dog", "hound"]);
+    }
+
+    #[test]
+    fn parses_kanji() {
+        let row = json!([
+            "日",
+            "ニチ ジツ",
+            "ひ か",
+            "jouyou",
+            ["day", "sun"],
+            {"strokes": "4", "grade": "1", "freq": "1"}
+        ]);
+        let info = parse_kanji(&row).unwrap();
+        assert_eq!(info.literal, '日');
+        assert_eq!(info.on_readings, ["ニチ", "ジツ"]);
+        assert_eq!(info.kun_readings, ["ひ", "か"]);
+        assert_eq!(info.meanings, ["day", "sun"]);
+        assert_eq!(info.stroke_count, Some(4));
+        assert_eq!(info.grade, Some(1));
+        assert_eq!(info.jlpt, None);
+        assert_eq!(info.frequency, Some(1));
+
+        let row = json!(["月", "ゲツ", "つき", "", "moon", "month"]);
+        let info = parse_kanji(&row).unwrap();
+        assert_eq!(info.meanings, ["moon", "month"]);
+        assert_eq!(info.stroke_count, None);
+    }
+
+    #[test]
+    fn parses_frequencies() {
+        let (expression, frequency) = parse_frequency(&json!(["猫", "freq", 42])).unwrap();
+        assert_eq!(expression, "猫");
+        assert_eq!(frequency.reading, None);
+        assert_eq!(frequency.value, "42");
+
+        let row = json!(["人", "freq", {"reading": "ひと", "frequency": {"value": 7, "displayValue": "7㋕"}}]);
+        let (_, frequency) = parse_frequency(&row).unwrap();
+        assert_eq!(frequency.reading.as_deref(), Some("ひと"));
+        assert_eq!(frequency.value, "7㋕");
+
+        let row = json!(["人", "freq", {"value": 7}]);
+        assert_eq!(parse_frequency(&row).unwrap().1.value, "7");
+
+        assert!(parse_frequency(&json!(["人", "pitch", {"reading": "ひと"}])).is_none());
+    }
+}
@@ -0,0 +1,240 @@
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use image::RgbaImage;
+
+use crate::ocr::jpn::{JpnText, VisualMorpheme};
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// the morphemes of a text, grouped by the line they were read on
+fn morphemes_per_line(jpn_text: &JpnText) -> Vec<Vec<&VisualMorpheme>> {
+    let mut lines = vec![vec![]; jpn_text.lines.len()];
+    let mut line_index = 0;
+    for vm in &jpn_text.morphemes {
+        // morphemes without a bounding box of their own stay on the line of the previous one
+        if let Some((x, y, w, h)) = vm.bbox.filter(|bbox| bbox.2 > 0) {
+            let (cx, cy) = (x + w / 2, y + h / 2);
+            if let Some(i) = jpn_text.lines.iter().position(|&(lx, ly, lw, lh)| {
+                lx <= cx && cx <= lx + lw && ly <= cy && cy <= ly + lh
+            }) {
+                line_index = i;
+            }
+        }
+        if let Some(line) = lines.get_mut(line_index) {
+            line.push(vm);
+        }
+    }
+    lines
+}
+
+fn morpheme_bbox(vm: &VisualMorpheme, line: (i32, i32, i32, i32)) -> (i32, i32, i32, i32) {
+    vm.bbox.filter(|bbox| bbox.2 > 0).unwrap_or(line)
+}
+
+/// hOCR document of the recognized texts; readings, lemmas and parts of speech as `data-` attributes of the words
+pub fn to_hocr(jpn_texts: &[JpnText], image_file: &str, width: u32, height: u32) -> String {
+    let mut hocr = String::new();
+    let _ = writeln!(
+        hocr,
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Transitional//EN" "http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd">
+<html xmlns="http://www.w3.org/1999/xhtml" xml:lang="ja" lang="ja">
+<head>
+<title></title>
+<meta http-equiv="Content-Type" content="text/html;charset=utf-8"/>
+<meta name="ocr-system" content="kanjisabi"/>
+<meta name="ocr-capabilities" content="ocr_page ocr_par ocr_line ocrx_word"/>
+</head>
+<body>
+<div class="ocr_page" id="page_1" title="image &quot;{}&quot;; bbox 0 0 {} {}">"#,
+        escape(image_file),
+        width,
+        height
+    );
+
+    for (p, jpn_text) in jpn_texts.iter().enumerate() {
+        let _ = writeln!(
+            hocr,
+            r#"<p class="ocr_par" id="par_{}" title="bbox {} {} {} {}">"#,
+            p + 1,
+            jpn_text.lines.iter().map(|l| l.0).min().unwrap_or_default(),
+            jpn_text.lines.iter().map(|l| l.1).min().unwrap_or_default(),
+            jpn_text
+                .lines
+                .iter()
+                .map(|l| l.0 + l.2)
+                .max()
+                .unwrap_or_default(),
+            jpn_text
+                .lines
+                .iter()
+                .map(|l| l.1 + l.3)
+                .max()
+                .unwrap_or_default(),
+        );
+        for (l, (line, morphemes)) in jpn_text
+            .lines
+            .iter()
+            .zip(morphemes_per_line(jpn_text))
+            .enumerate()
+        {
+            let (x, y, w, h) = *line;
+            let _ = writeln!(
+                hocr,
+                r#"<span class="ocr_line" id="line_{}_{}" title="bbox {} {} {} {}">"#,
+                p + 1,
+                l + 1,
+                x,
+                y,
+                x + w,
+                y + h
+            );
+            for (m, vm) in morphemes.iter().enumerate() {
+                let (x, y, w, h) = morpheme_bbox(vm, *line);
+                let morpheme = &vm.morpheme;
+                let _ = writeln!(
+                    hocr,
+                    r#"<span class="ocrx_word" id="word_{}_{}_{}" title="bbox {} {} {} {}" data-reading="{}" data-lemma="{}" data-pos="{}">{}</span>"#,
+                    p + 1,
+                    l + 1,
+                    m + 1,
+                    x,
+                    y,
+                    x + w,
+                    y + h,
+                    escape(&morpheme.pronounciation),
+                    escape(&morpheme.lemma),
+                    escape(&morpheme.part_of_speech),
+                    escape(&morpheme.text)
+                );
+            }
+            let _ = writeln!(hocr, "</span>");
+        }
+        let _ = writeln!(hocr, "</p>");
+    }
+
+    let _ = writeln!(hocr, "</div>\n</body>\n</html>");
+    hocr
+}
+
+/// ALTO (v4) document of the recognized texts; readings and lemmas as `ALTERNATIVE`s of the strings
+pub fn to_alto(jpn_texts: &[JpnText], image_file: &str, width: u32, height: u32) -> String {
+    let mut alto = String::new();
+    let _ = writeln!(
+        alto,
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<alto xmlns="http://www.loc.gov/standards/alto/ns-v4#" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="http://www.loc.gov/standards/alto/ns-v4# http://www.loc.gov/alto/v4/alto-4-2.xsd">
+<Description>
+<MeasurementUnit>pixel</MeasurementUnit>
+<sourceImageInformation><fileName>{}</fileName></sourceImageInformation>
+<OCRProcessing ID="OCR_0"><ocrProcessingStep><processingSoftware><softwareName>kanjisabi</softwareName></processingSoftware></ocrProcessingStep></OCRProcessing>
+</Description>
+<Layout>
+<Page ID="page_1" PHYSICAL_IMG_NR="1" WIDTH="{}" HEIGHT="{}">
+<PrintSpace HPOS="0" VPOS="0" WIDTH="{}" HEIGHT="{}">"#,
+        escape(image_file),
+        width,
+        height,
+        width,
+        height
+    );
+
+    for (b, jpn_text) in jpn_texts.iter().enumerate() {
+        let x0 = jpn_text.lines.iter().map(|l| l.0).min().unwrap_or_default();
+        let y0 = jpn_text.lines.iter().map(|l| l.1).min().unwrap_or_default();
+        let x1 = jpn_text
+            .lines
+            .iter()
+            .map(|l| l.0 + l.2)
+            .max()
+            .unwrap_or_default();
+        let y1 = jpn_text
+            .lines
+            .iter()
+            .map(|l| l.1 + l.3)
+            .max()
+            .unwrap_or_default();
+        let _ = writeln!(
+            alto,
+            r#"<TextBlock ID="block_{}" HPOS="{}" VPOS="{}" WIDTH="{}" HEIGHT="{}" LANG="ja">"#,
+            b + 1,
+            x0,
+            y0,
+            x1 - x0,
+            y1 - y0
+        );
+        for (l, (line, morphemes)) in jpn_text
+            .lines
+            .iter()
+            .zip(morphemes_per_line(jpn_text))
+            .enumerate()
+        {
+            let (x, y, w, h) = *line;
+            let _ = writeln!(
+                alto,
+                r#"<TextLine ID="line_{}_{}" HPOS="{}" VPOS="{}" WIDTH="{}" HEIGHT="{}">"#,
+                b + 1,
+                l + 1,
+                x,
+                y,
+                w,
+                h
+            );
+            for (s, vm) in morphemes.iter().enumerate() {
+                let (x, y, w, h) = morpheme_bbox(vm, *line);
+                let morpheme = &vm.morpheme;
+                let _ = writeln!(
+                    alto,
+                    r#"<String ID="string_{}_{}_{}" CONTENT="{}" HPOS="{}" VPOS="{}" WIDTH="{}" HEIGHT="{}"><ALTERNATIVE PURPOSE="reading">{}</ALTERNATIVE><ALTERNATIVE PURPOSE="lemma">{}</ALTERNATIVE></String>"#,
+                    b + 1,
+                    l + 1,
+                    s + 1,
+                    escape(&morpheme.text),
+                    x,
+                    y,
+                    w,
+                    h,
+                    escape(&morpheme.pronounciation),
+                    escape(&morpheme.lemma)
+                );
+            }
+            let _ = writeln!(alto, "</TextLine>");
+        }
+        let _ = writeln!(alto, "</TextBlock>");
+    }
+
+    let _ = writeln!(alto, "</PrintSpace>\n</Page>\n</Layout>\n</alto>");
+    alto
+}
+
+/// save a capture along with its hOCR and ALTO recognition results in `dir`, returning the path of the image
+pub fn save_capture(dir: &Path, capture: &RgbaImage, jpn_texts: &[JpnText]) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    let stem = format!("capture-{}", timestamp);
+    let image_file = format!("{}.png", stem);
+    let (width, height) = capture.dimensions();
+
+    let image_path = dir.join(&image_file);
+    capture.save(&image_path)?;
+    std::fs::write(
+        dir.join(format!("{}.hocr", stem)),
+        to_hocr(jpn_texts, &image_file, width, height),
+    )?;
+    std::fs::write(
+        dir.join(format!("{}.alto.xml", stem)),
+        to_alto(jpn_texts, &image_file, width, height),
+    )?;
+
+    Ok(image_path)
+}
@@ -1,4 +1,13 @@
 pub mod config;
+pub mod dict;
+pub mod export;
 pub mod fonts;
 pub mod ocr;
+pub mod offline;
 pub mod overlay;
+pub mod pin;
+pub mod pipeline;
+pub mod preproc;
+pub mod region;
+pub mod screen;
+pub mod script;
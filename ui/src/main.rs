@@ -1,36 +1,114 @@
 #![feature(iter_intersperse)]
 
 extern crate device_query;
-extern crate screenshot;
-extern crate tesseract;
 
 use anyhow::Result;
+use clap::{Parser, Subcommand};
 use device_query::{DeviceQuery, DeviceState, Keycode};
 use fontconfig::Fontconfig;
-use image::{ImageBuffer, Rgba};
+use image::{imageops, RgbaImage};
 use kanjisabi::config::{load_config, watch_config, KSConfig};
+use kanjisabi::dict::glosses::{load_jmnedict, load_yomitan, Dictionaries};
+use kanjisabi::dict::jmnedict::Jmnedict;
+use kanjisabi::dict::kanjidic::Kanjidic;
+use kanjisabi::dict::radicals::Radicals;
+use kanjisabi::dict::yomitan::YomitanDictionary;
+use kanjisabi::dict::{Dictionary, LookupParams};
+use kanjisabi::export::save_capture;
 use kanjisabi::fonts::{japanese_font_families_and_styles_flat, path_to_font};
-use kanjisabi::ocr::jpn::JpnText;
-use kanjisabi::ocr::jpn::{print_jmdict_results, JpnOCR};
+use kanjisabi::ocr::cache::{cache_key, OCRCache};
+use kanjisabi::ocr::jpn::{JpnOCR, JpnText};
+use kanjisabi::ocr::layout::LayoutParams;
+use kanjisabi::offline::OfflineOCR;
 use kanjisabi::overlay::sdl::{argb_to_sdl_color, print_to_new_pixels, TextMeta};
 use kanjisabi::overlay::x11::{
-    create_overlay_fullscreen_window, draw_a_rectangle, paint_rgba_pixels_on_window, raise,
-    with_name, xfixes_init,
+    create_overlay_window, draw_a_rectangle, find_client_window, paint_rgba_pixels_on_window,
+    pick_window, raise, resize_window, with_name, xfixes_init,
 };
+use kanjisabi::pin::{PinParams, PinnedRegion};
+use kanjisabi::pipeline::recognize_preprocessed;
+use kanjisabi::preproc::preprocess;
+use kanjisabi::region::{text_block_at, RegionParams};
+use kanjisabi::screen::anchor::Anchor;
+use kanjisabi::screen::capture::ScreenCapture;
+use kanjisabi::screen::{capture_area, extent, monitor_at, monitors, randr_init, Monitor};
+use kanjisabi::script::{is_kanji, ScriptPolicy};
 use log::{debug, info, trace, warn};
-use morph::JpnMorphAnalysisAPI;
-use screenshot::get_screenshot_area;
+use morph::{JpnMorphAnalysisAPI, Morpheme};
 use sdl2::ttf::{FontStyle, Sdl2TtfContext};
+use std::hash::Hash;
 use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, Sender};
 use std::time;
 use x11rb::connection::Connection;
 use x11rb::protocol::xproto::{ConnectionExt as _, Window};
+use x11rb::protocol::Event;
 use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Recognize and analyze image files, writing JSON results and annotated images
+    Files {
+        /// Output directory; next to each image by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        #[arg(required = true)]
+        images: Vec<PathBuf>,
+    },
+    /// Recognize and analyze the images saved into a directory, e.g. an emulator's screenshots
+    Watch {
+        /// Output directory; the watched directory by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        directory: PathBuf,
+    },
+}
 
 fn same_content<T: std::cmp::PartialEq>(ts0: &[T], ts1: &[T]) -> bool {
     ts0.len() == ts1.len() && ts0.iter().all(|t| ts1.contains(t))
 }
 
+/// a dictionary loaded on another thread, to be swapped in by the event loop
+enum Loaded {
+    Kanjidic(Option<Kanjidic>),
+    Jmnedict(Option<Jmnedict>),
+    Yomitan(Vec<YomitanDictionary>),
+}
+
+fn load_kanjidic(path: Option<PathBuf>) -> Option<Kanjidic> {
+    let path = path?;
+    match Kanjidic::load(&path) {
+        Ok(kanjidic) => Some(kanjidic),
+        Err(e) => {
+            warn!("Failed to load KANJIDIC2 from {:?}: {:?}", path, e);
+            None
+        }
+    }
+}
+
+fn load_radicals(config: &KSConfig) -> Option<Radicals> {
+    let kradfile = config.kanji.kradfile();
+    let radkfile = config.kanji.radkfile();
+    if kradfile.is_none() && radkfile.is_none() {
+        return None;
+    }
+    match Radicals::load(kradfile.as_deref(), radkfile.as_deref()) {
+        Ok(radicals) => Some(radicals),
+        Err(e) => {
+            warn!("Failed to load KRADFILE/RADKFILE: {:?}", e);
+            None
+        }
+    }
+}
+
 fn get_font_path(config: &KSConfig) -> PathBuf {
     let fc = Fontconfig::new().unwrap();
     let fonts = japanese_font_families_and_styles_flat(&fc);
@@ -66,35 +144,80 @@ fn get_font_path(config: &KSConfig) -> PathBuf {
 
 struct App {
     // program constants
-    screen_w: u16,
-    screen_h: u16,
     config: KSConfig,
     // helpers
     sdl2_ttf_ctx: Sdl2TtfContext,
     ocr: JpnOCR,
+    kanjidic: Option<Kanjidic>,
+    /// dictionaries loaded on other threads are sent through this channel
+    loaded_tx: Sender<Loaded>,
+    loaded_rx: Receiver<Loaded>,
+    radicals: Option<Radicals>,
+    dictionaries: Dictionaries,
+    cache: OCRCache,
+    screen_capture: ScreenCapture,
     // states
     conn: RustConnection,
+    root: Window,
     window: Window,
+    monitors: Vec<Monitor>,
+    /// the overlay's size, spanning all monitors
+    screen_w: u16,
+    screen_h: u16,
     capture_x0: i32,
     capture_y0: i32,
     capture_x1: i32,
     capture_y1: i32,
     ocr_results: Vec<JpnText>,
+    last_capture: Option<RgbaImage>,
     result_index: usize,
     morpheme_index: usize,
+    /// the page of the selected morpheme's senses on display
+    sense_page: usize,
+    /// the kanji of the selected morpheme detailed
+    kanji_index: usize,
     font_scale: i32,
     font_path: PathBuf,
+    hover_enabled: bool,
+    window_mapped: bool,
+    pinned: Option<PinnedRegion>,
+    anchor: Option<Anchor>,
+    /// events received while a window was being picked, handled along with the next ones
+    skipped_events: Vec<Event>,
 }
 
 impl App {
-    fn reload_config(&mut self, window_mapped: bool) -> Result<()> {
+    fn reload_config(&mut self) -> Result<()> {
         info!("Configuration changed, refreshing...");
-        let old_contrast = self.config.preproc.contrast;
+        let old_ocr_settings = self.ocr_settings();
+        let old_kanjidic = self.config.kanji.kanjidic();
+        let old_radicals = (self.config.kanji.kradfile(), self.config.kanji.radkfile());
+        let old_jmnedict = self.config.names.jmnedict();
+        let old_yomitan = self.config.yomitan.dictionaries.clone();
         self.config = load_config().unwrap_or_default();
+        if self.config.kanji.kanjidic() != old_kanjidic {
+            let path = self.config.kanji.kanjidic();
+            self.load_in_background(move || Loaded::Kanjidic(load_kanjidic(path)));
+        }
+        if (self.config.kanji.kradfile(), self.config.kanji.radkfile()) != old_radicals {
+            self.radicals = load_radicals(&self.config);
+        }
+        if self.config.names.jmnedict() != old_jmnedict {
+            let path = self.config.names.jmnedict();
+            self.load_in_background(move || Loaded::Jmnedict(load_jmnedict(path)));
+        }
+        if self.config.yomitan.dictionaries != old_yomitan {
+            let sources = self.config.yomitan.dictionaries.clone();
+            self.load_in_background(move || Loaded::Yomitan(load_yomitan(&sources)));
+        }
         self.font_path = get_font_path(&self.config);
-        if window_mapped {
-            let new_contrast = self.config.preproc.contrast;
-            if old_contrast != new_contrast {
+        self.cache.set_capacity(self.config.cache.capacity);
+        self.ocr
+            .set_script_policy(ScriptPolicy::from(&self.config.script));
+        self.ocr
+            .set_layout_params(LayoutParams::from(&self.config.layout));
+        if self.window_mapped {
+            if old_ocr_settings != self.ocr_settings() {
                 self.reset_ocr()?;
                 self.draw_capture_area()?;
                 self.perform_ocr()?;
@@ -106,6 +229,81 @@ impl App {
         Ok(())
     }
 
+    /// load a dictionary without blocking the event loop
+    fn load_in_background(&self, load: impl FnOnce() -> Loaded + Send + 'static) {
+        let loaded_tx = self.loaded_tx.clone();
+        std::thread::spawn(move || {
+            let _ = loaded_tx.send(load());
+        });
+    }
+
+    /// swap in the dictionaries done loading, and show what they have to say of the current hint
+    fn swap_loaded(&mut self) -> Result<()> {
+        let mut swapped = false;
+        while let Ok(loaded) = self.loaded_rx.try_recv() {
+            match loaded {
+                Loaded::Kanjidic(kanjidic) => self.kanjidic = kanjidic,
+                Loaded::Jmnedict(jmnedict) => self.dictionaries.jmnedict = jmnedict,
+                Loaded::Yomitan(yomitan) => self.dictionaries.yomitan = yomitan,
+            }
+            swapped = true;
+        }
+        if swapped && self.window_mapped && !self.ocr_results.is_empty() {
+            self.redraw_all()?;
+        }
+        Ok(())
+    }
+
+    fn map_overlay(&mut self) -> Result<()> {
+        if !self.window_mapped {
+            debug!("mapping overlay");
+            self.conn.map_window(self.window)?;
+            raise(&self.conn, self.window)?;
+            self.window_mapped = true;
+        }
+        Ok(())
+    }
+
+    fn unmap_overlay(&mut self) -> Result<()> {
+        if self.window_mapped {
+            debug!("hiding overlay");
+            self.conn.unmap_window(self.window)?;
+            self.conn.flush()?;
+            self.window_mapped = false;
+        }
+        Ok(())
+    }
+
+    /// capture the area `(x, y, w, h)` of the screen, without the overlay: it is hidden meanwhile so that
+    /// recognition never sees our own drawings
+    fn capture(&mut self, x: i32, y: i32, w: u32, h: u32) -> Result<RgbaImage> {
+        let was_mapped = self.window_mapped;
+        if was_mapped {
+            self.unmap_overlay()?;
+            // wait for the server to process the unmapping, then for a compositor to repaint the screen
+            self.conn.sync()?;
+            std::thread::sleep(time::Duration::from_millis(
+                self.config.capture.compositor_delay,
+            ));
+        }
+
+        let img = capture_area(
+            &self.conn,
+            &mut self.screen_capture,
+            &self.monitors,
+            x,
+            y,
+            w,
+            h,
+        );
+
+        if was_mapped {
+            self.map_overlay()?;
+            self.redraw_all()?;
+        }
+        img
+    }
+
     fn clear_overlay(&self) -> Result<()> {
         draw_a_rectangle(
             &self.conn,
@@ -126,18 +324,28 @@ impl App {
         self.ocr_results.clear();
         self.result_index = 0;
         self.morpheme_index = 0;
+        self.sense_page = 0;
+        self.kanji_index = 0;
         self.clear_overlay()?;
         Ok(())
     }
 
-    fn redraw_all(&self) -> Result<()> {
+    fn redraw_all(&mut self) -> Result<()> {
         self.clear_overlay()?;
         self.draw_capture_area()?;
         self.draw_highlights()?;
         self.draw_hint()?;
+        self.overlay_redrawn();
         Ok(())
     }
 
+    /// the overlay's drawings show in the pinned region's pixels, which must not pass for a change of its text
+    fn overlay_redrawn(&mut self) {
+        if let Some(pin) = &mut self.pinned {
+            pin.set_reference(None);
+        }
+    }
+
     fn draw_capture_area(&self) -> Result<()> {
         let x = std::cmp::min(self.capture_x0, self.capture_x1) as i16;
         let y = std::cmp::min(self.capture_y0, self.capture_y1) as i16;
@@ -159,15 +367,17 @@ impl App {
     }
 
     fn draw_highlight(&self, jpn_text: &JpnText, x0: i16, y0: i16) -> Result<()> {
-        draw_a_rectangle(
-            &self.conn,
-            self.window,
-            x0 + jpn_text.x as i16,
-            y0 + jpn_text.y as i16,
-            jpn_text.w as u16,
-            jpn_text.h as u16,
-            self.config.colors.highlight,
-        )?;
+        for (x, y, w, h) in &jpn_text.lines {
+            draw_a_rectangle(
+                &self.conn,
+                self.window,
+                x0 + *x as i16,
+                y0 + *y as i16,
+                *w as u16,
+                *h as u16,
+                self.config.colors.highlight,
+            )?;
+        }
 
         Ok(())
     }
@@ -208,18 +418,18 @@ impl App {
             ..text_meta
         };
 
-        let morpheme = &jpn_text.morphemes[morpheme_index].morpheme;
-        println!("text: {}", morpheme.text);
-        println!("pos: {}", morpheme.part_of_speech);
-        println!(
-            "form: {}",
-            morpheme.inflection_form.as_deref().unwrap_or_default()
-        );
-        println!(
-            "type: {}",
+        let v_morpheme = match jpn_text.morphemes.get(morpheme_index) {
+            Some(v_morpheme) => v_morpheme,
+            None => return Ok(()),
+        };
+        let morpheme = &v_morpheme.morpheme;
+        debug!(
+            "morpheme: {} ({}, {}, {})",
+            morpheme.text,
+            morpheme.part_of_speech,
+            morpheme.inflection_form.as_deref().unwrap_or_default(),
             morpheme.inflection_type.as_deref().unwrap_or_default()
         );
-        print_jmdict_results(&morpheme.text);
 
         let with_seps = jpn_text
             .morphemes
@@ -274,9 +484,17 @@ impl App {
         {
             let mut y = y + height_mph as i32;
 
+            // flag furigana disagreeing with the analyzed reading
+            let reading = match &v_morpheme.reading_hint {
+                Some(hint) if !hint.agrees => {
+                    format!("{} ≠ {}", morpheme.pronounciation, hint.text)
+                }
+                _ => morpheme.pronounciation.clone(),
+            };
+
             let (data_pronounc, width_pronounc, height_pronounc) = print_to_new_pixels(
                 &self.sdl2_ttf_ctx,
-                &morpheme.pronounciation,
+                &reading,
                 &text_meta,
                 self.config.colors.hint_bg,
                 0,
@@ -331,6 +549,31 @@ impl App {
                 width_pos,
                 height_pos,
             )?;
+
+            y += height_pos as i32;
+
+            let lines = self.gloss_lines(morpheme);
+            for line in lines.into_iter().chain(self.kanji_lines(morpheme)) {
+                let (data_gloss, width_gloss, height_gloss) = print_to_new_pixels(
+                    &self.sdl2_ttf_ctx,
+                    &line,
+                    &text_meta,
+                    self.config.colors.hint_bg,
+                    0,
+                );
+
+                paint_rgba_pixels_on_window(
+                    &self.conn,
+                    self.window,
+                    &data_gloss,
+                    x,
+                    y,
+                    width_gloss,
+                    height_gloss,
+                )?;
+
+                y += height_gloss as i32;
+            }
         }
 
         x += width_mph as i32;
@@ -356,6 +599,125 @@ impl App {
         Ok(())
     }
 
+    /// the form the morpheme's entry was found with, its frequencies, then the current page of its senses,
+    /// one line per sense
+    fn gloss_lines(&self, morpheme: &Morpheme) -> Vec<String> {
+        const MAX_LINE_CHARS: usize = 60;
+        let params = LookupParams::from(&self.config.glosses);
+        let glosses = match self.dictionaries.glosses(morpheme, &params) {
+            Some(glosses) => glosses,
+            None => return vec![],
+        };
+        let senses = &glosses.senses;
+        let page_size = std::cmp::max(self.config.glosses.max_senses, 1);
+        let pages = (senses.len() + page_size - 1) / page_size;
+        let first = self.sense_page % pages * page_size;
+
+        let mut lines = vec![format!(
+            "{} (found via {} in {})",
+            glosses.headword, glosses.key, glosses.dictionary
+        )];
+        let frequencies: Vec<String> = self
+            .dictionaries
+            .frequencies(morpheme)
+            .into_iter()
+            .map(|(title, frequency)| format!("{} {}", title, frequency))
+            .collect();
+        if !frequencies.is_empty() {
+            lines.push(format!("frequency: {}", frequencies.join(", ")));
+        }
+        lines.extend(
+            senses
+                .iter()
+                .enumerate()
+                .skip(first)
+                .take(page_size)
+                .map(|(i, glosses)| {
+                    let line = format!("{}. {}", i + 1, glosses.join("; "));
+                    if line.chars().count() > MAX_LINE_CHARS {
+                        line.chars()
+                            .take(MAX_LINE_CHARS - 1)
+                            .chain(std::iter::once('…'))
+                            .collect()
+                    } else {
+                        line
+                    }
+                }),
+        );
+        if pages > 1 {
+            lines.push(format!("({}/{})", self.sense_page % pages + 1, pages));
+        }
+        lines
+    }
+
+    /// KANJIDIC2's information, or else a Yomitan kanji dictionary's, on the detailed kanji of the morpheme,
+    /// and its radicals
+    fn kanji_lines(&self, morpheme: &Morpheme) -> Vec<String> {
+        if self.kanjidic.is_none()
+            && self.radicals.is_none()
+            && self.dictionaries.yomitan.is_empty()
+        {
+            return vec![];
+        }
+        let kanji: Vec<char> = morpheme.text.chars().filter(|&c| is_kanji(c)).collect();
+        if kanji.is_empty() {
+            return vec![];
+        }
+        let literal = kanji[self.kanji_index % kanji.len()];
+        let info = self
+            .kanjidic
+            .as_ref()
+            .and_then(|k| k.get(literal))
+            .or_else(|| {
+                self.dictionaries
+                    .yomitan
+                    .iter()
+                    .find_map(|d| d.kanji(literal))
+            });
+
+        let mut lines = vec![match info {
+            Some(info) => format!("{} {}", literal, info.meanings.join(", ")),
+            None => literal.to_string(),
+        }];
+        if let Some(info) = info {
+            let readings: Vec<String> = [("on", &info.on_readings), ("kun", &info.kun_readings)]
+                .iter()
+                .filter(|(_, readings)| !readings.is_empty())
+                .map(|(label, readings)| format!("{}: {}", label, readings.join("、")))
+                .collect();
+            if !readings.is_empty() {
+                lines.push(readings.join("  "));
+            }
+            let numbers: Vec<String> = [
+                ("strokes", info.stroke_count),
+                ("grade", info.grade),
+                ("JLPT", info.jlpt),
+                ("frequency", info.frequency),
+            ]
+            .iter()
+            .filter_map(|(label, n)| n.map(|n| format!("{} {}", label, n)))
+            .collect();
+            if !numbers.is_empty() {
+                lines.push(numbers.join(", "));
+            }
+        }
+        if let Some(radicals) = &self.radicals {
+            let components = radicals.radicals(literal);
+            if !components.is_empty() {
+                let components: Vec<String> = components.iter().map(char::to_string).collect();
+                lines.push(format!("components: {}", components.join(" ")));
+            }
+        }
+        if kanji.len() > 1 {
+            lines.push(format!(
+                "({}/{})",
+                self.kanji_index % kanji.len() + 1,
+                kanji.len()
+            ));
+        }
+        lines
+    }
+
     fn draw_hint(&self) -> Result<()> {
         if let Some(jpn_text) = self.ocr_results.get(self.result_index) {
             let x0 = std::cmp::min(self.capture_x0, self.capture_x1);
@@ -368,17 +730,69 @@ impl App {
         Ok(())
     }
 
+    /// the settings which, besides the captured pixels, affect recognition results
+    fn ocr_settings(&self) -> impl Hash + PartialEq {
+        (
+            self.config.preproc.contrast.to_bits(),
+            self.config.preproc.deskew,
+            self.config.preproc.max_skew_angle.to_bits(),
+            ScriptPolicy::from(&self.config.script),
+            self.config.layout.join_lines,
+            self.config.layout.max_line_gap.to_bits(),
+            self.config.layout.ruby_ratio.to_bits(),
+        )
+    }
+
     fn perform_ocr(&mut self) -> Result<()> {
-        let x = std::cmp::min(self.capture_x0, self.capture_x1) as u32;
-        let y = std::cmp::min(self.capture_y0, self.capture_y1) as u32;
+        let x = std::cmp::min(self.capture_x0, self.capture_x1);
+        let y = std::cmp::min(self.capture_y0, self.capture_y1);
         let w = (self.capture_x0 - self.capture_x1).unsigned_abs();
         let h = (self.capture_y0 - self.capture_y1).unsigned_abs();
 
-        let ocr_area = get_screenshot_area(0, x, y, w, h).unwrap();
+        let img = self.capture(x, y, w, h)?;
 
-        let mut img: ImageBuffer<Rgba<u8>, Vec<u8>> =
-            ImageBuffer::from_vec(w, h, ocr_area.as_ref().to_vec()).unwrap();
-        image::imageops::colorops::contrast_in_place(&mut img, self.config.preproc.contrast);
+        self.ocr_capture(img)?;
+
+        self.draw_highlights()?;
+
+        self.draw_hint()?;
+
+        self.overlay_redrawn();
+
+        // self.draw_translations();
+
+        Ok(())
+    }
+
+    /// capture the surroundings of the cursor and select the text block under it as the capture area;
+    /// returns the pixels of the block
+    fn detect_text_region(&mut self, (px, py): (i32, i32)) -> Result<Option<RgbaImage>> {
+        let monitor = match monitor_at(&self.monitors, px, py) {
+            Some(monitor) => *monitor,
+            None => return Ok(None),
+        };
+        let w = std::cmp::min(self.config.region.search_width, monitor.w);
+        let h = std::cmp::min(self.config.region.search_height, monitor.h);
+        let (x, y) = monitor.fit(px, py, w, h);
+
+        let img = self.capture(x, y, w, h)?;
+
+        let params = RegionParams::from(&self.config.region);
+        let block = text_block_at(&img, px - x, py - y, &params);
+        Ok(block.map(|(bx, by, bw, bh)| {
+            debug!("text block found at {:?}", (x + bx, y + by, bw, bh));
+            (self.capture_x0, self.capture_y0) = (x + bx, y + by);
+            (self.capture_x1, self.capture_y1) = (x + bx + bw, y + by + bh);
+            imageops::crop_imm(&img, bx as u32, by as u32, bw as u32, bh as u32).to_image()
+        }))
+    }
+
+    fn ocr_capture(&mut self, img: RgbaImage) -> Result<()> {
+        self.last_capture = Some(img.clone());
+        let (img, deskew) = preprocess(img, &self.config.preproc);
+        if let Some(deskew) = deskew {
+            debug!("deskewing capture by {}°", deskew.angle.to_degrees());
+        }
 
         // visual debug, re-paint captured area after pre-processing
         // paint_rgba_pixels_on_window(
@@ -387,29 +801,70 @@ impl App {
         //     img.as_raw(),
         //     self.capture_x,
         //     self.capture_y,
-        //     w as u32,
-        //     h as u32,
+        //     img.width(),
+        //     img.height(),
         // )
         // .unwrap();
 
-        // attempt recognition
-        self.ocr_results = self
-            .ocr
-            .recognize(
-                img.as_raw(),
-                ocr_area.width() as i32,
-                ocr_area.height() as i32,
-                ocr_area.pixel_width() as i32,
-                ocr_area.pixel_width() as i32 * ocr_area.width() as i32,
-            )
-            .unwrap_or_default();
+        let key = cache_key(img.as_raw(), img.width(), img.height(), self.ocr_settings());
+        if let Some(cached) = self.cache.get(key) {
+            debug!("OCR cache hit");
+            self.ocr_results = cached.clone();
+        } else {
+            // attempt recognition
+            match recognize_preprocessed(&mut self.ocr, &img, deskew) {
+                Ok(ocr_results) => {
+                    self.cache.insert(key, ocr_results.clone());
+                    self.ocr_results = ocr_results;
+                }
+                Err(_) => self.ocr_results = vec![],
+            }
+        }
 
-        self.draw_highlights()?;
+        Ok(())
+    }
 
-        self.draw_hint()?;
+    /// recognize the text around the cursor and select the morpheme under it; whether there is one
+    fn hover_lookup(&mut self, (px, py): (i32, i32)) -> Result<bool> {
+        let monitor = match monitor_at(&self.monitors, px, py) {
+            Some(monitor) => *monitor,
+            None => return Ok(false),
+        };
+        let w = std::cmp::min(self.config.hover.width, monitor.w);
+        let h = std::cmp::min(self.config.hover.height, monitor.h);
+        let (x, y) = monitor.fit(px, py, w, h);
 
-        // self.draw_translations();
+        let img = self.capture(x, y, w, h)?;
+
+        (self.capture_x0, self.capture_y0) = (x, y);
+        (self.capture_x1, self.capture_y1) = (x + w as i32, y + h as i32);
+        self.ocr_capture(img)?;
 
+        let hit = self
+            .ocr_results
+            .iter()
+            .enumerate()
+            .find_map(|(i, jpn_text)| jpn_text.morpheme_at(px - x, py - y).map(|m| (i, m)));
+        if let Some((result_index, morpheme_index)) = hit {
+            self.result_index = result_index;
+            self.morpheme_index = morpheme_index;
+            self.sense_page = 0;
+            self.kanji_index = 0;
+        }
+        Ok(hit.is_some())
+    }
+
+    /// follow monitors being plugged, unplugged or rearranged, keeping the overlay over all of them
+    fn refresh_monitors(&mut self) -> Result<()> {
+        self.monitors = monitors(&self.conn, self.root)?;
+        debug!("monitors: {:?}", self.monitors);
+        let (screen_w, screen_h) = extent(&self.monitors);
+        if (screen_w, screen_h) != (self.screen_w, self.screen_h) {
+            info!("Screen resized to {}x{}", screen_w, screen_h);
+            (self.screen_w, self.screen_h) = (screen_w, screen_h);
+            resize_window(&self.conn, self.window, screen_w as u32, screen_h as u32)?;
+            self.conn.flush()?;
+        }
         Ok(())
     }
 
@@ -437,6 +892,197 @@ impl App {
         same_content(keys, &self.config.keys.next_morpheme)
     }
 
+    fn next_kanji(&self, keys: &[Keycode]) -> bool {
+        same_content(keys, &self.config.keys.next_kanji)
+    }
+
+    fn next_senses(&self, keys: &[Keycode]) -> bool {
+        same_content(keys, &self.config.keys.next_senses)
+    }
+
+    fn next_alternative(&self, keys: &[Keycode]) -> bool {
+        same_content(keys, &self.config.keys.next_alternative)
+    }
+
+    /// replace the least confident character of the selected morpheme with its next candidate
+    fn cycle_alternative(&mut self) -> Result<()> {
+        if let Some(jpn_text) = self.ocr_results.get_mut(self.result_index) {
+            if let Some(char_index) = jpn_text.uncertain_char(self.morpheme_index) {
+                self.ocr.cycle_alternative(jpn_text, char_index);
+                debug!("corrected text: {}", jpn_text.text());
+                // the corrected character may now belong to a different morpheme
+                self.morpheme_index = jpn_text
+                    .morphemes
+                    .iter()
+                    .scan(0, |end, vm| {
+                        *end += vm.morpheme.text.chars().count();
+                        Some(*end)
+                    })
+                    .position(|end| end > char_index)
+                    .unwrap_or_default();
+                self.sense_page = 0;
+                self.kanji_index = 0;
+                self.redraw_all()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn detect_region(&self, keys: &[Keycode]) -> bool {
+        same_content(keys, &self.config.keys.detect_region)
+    }
+
+    fn hover_toggle(&self, keys: &[Keycode]) -> bool {
+        same_content(keys, &self.config.keys.hover_toggle)
+    }
+
+    fn hover_modifier(&self, keys: &[Keycode]) -> bool {
+        !self.config.keys.hover_modifier.is_empty()
+            && same_content(keys, &self.config.keys.hover_modifier)
+    }
+
+    fn pin_toggle(&self, keys: &[Keycode]) -> bool {
+        same_content(keys, &self.config.keys.pin_toggle)
+    }
+
+    /// pin the capture area, to be recognized again whenever its content changes; or unpin it
+    fn toggle_pin(&mut self) {
+        if self.pinned.take().is_some() {
+            info!("Region unpinned");
+            return;
+        }
+        let x = std::cmp::min(self.capture_x0, self.capture_x1);
+        let y = std::cmp::min(self.capture_y0, self.capture_y1);
+        let w = (self.capture_x0 - self.capture_x1).unsigned_abs();
+        let h = (self.capture_y0 - self.capture_y1).unsigned_abs();
+        if !self.window_mapped || w == 0 || h == 0 {
+            info!("No capture area to pin");
+            return;
+        }
+        info!("Region {:?} pinned", (x, y, w, h));
+        let params = PinParams::from(&self.config.pin);
+        self.pinned = Some(PinnedRegion::new(x, y, w, h, params));
+    }
+
+    fn unpin(&mut self) {
+        if self.pinned.take().is_some() {
+            info!("Region unpinned");
+        }
+    }
+
+    /// recognize the pinned region again if its pixels changed, once they settle
+    fn watch_pinned(&mut self) -> Result<()> {
+        let (x, y, w, h) = match &self.pinned {
+            Some(pin) => (pin.x, pin.y, pin.w, pin.h),
+            None => return Ok(()),
+        };
+        // the overlay stays visible while polling, its drawings are part of the reference pixels
+        let img = capture_area(
+            &self.conn,
+            &mut self.screen_capture,
+            &self.monitors,
+            x,
+            y,
+            w,
+            h,
+        )?;
+        if self.pinned.as_mut().map_or(false, |pin| pin.poll(img)) {
+            debug!("pinned region changed, performing OCR");
+            self.reset_ocr()?;
+            self.perform_ocr()?;
+        }
+        Ok(())
+    }
+
+    fn anchor_toggle(&self, keys: &[Keycode]) -> bool {
+        same_content(keys, &self.config.keys.anchor_toggle)
+    }
+
+    /// bind the capture area to the configured window, or to the next one clicked; or release it
+    fn toggle_anchor(&mut self) -> Result<()> {
+        if self.anchor.is_some() {
+            return self.release_anchor();
+        }
+        let x = std::cmp::min(self.capture_x0, self.capture_x1);
+        let y = std::cmp::min(self.capture_y0, self.capture_y1);
+        let w = (self.capture_x0 - self.capture_x1).unsigned_abs();
+        let h = (self.capture_y0 - self.capture_y1).unsigned_abs();
+        if !self.window_mapped || w == 0 || h == 0 {
+            info!("No capture area to anchor");
+            return Ok(());
+        }
+        let window = match self.config.anchor.window() {
+            Some(query) => find_client_window(&self.conn, self.root, query),
+            None => {
+                info!("Click the window to anchor the capture area to");
+                let timeout = time::Duration::from_millis(self.config.anchor.pick_timeout);
+                pick_window(&self.conn, self.root, timeout, &mut self.skipped_events)
+            }
+        };
+        match window.and_then(|window| Anchor::new(&self.conn, self.root, window, (x, y, w, h))) {
+            Ok(anchor) => {
+                info!("Capture area anchored to window {:#x}", anchor.window);
+                self.anchor = Some(anchor);
+            }
+            Err(e) => warn!("Failed to anchor the capture area: {:?}", e),
+        }
+        Ok(())
+    }
+
+    fn release_anchor(&mut self) -> Result<()> {
+        if let Some(anchor) = self.anchor.take() {
+            info!("Capture area released from window {:#x}", anchor.window);
+            anchor.release(&self.conn)?;
+        }
+        Ok(())
+    }
+
+    /// move the capture area along with the anchor's window; whether it was resized, making results stale
+    fn follow_anchor(&mut self) -> Result<bool> {
+        let anchor = match &mut self.anchor {
+            Some(anchor) => anchor,
+            None => return Ok(false),
+        };
+        let (moved, resized) = match anchor.refresh(&self.conn, self.root) {
+            Ok(changes) => changes,
+            Err(e) => {
+                warn!("Lost track of the anchor window: {:?}", e);
+                self.release_anchor()?;
+                return Ok(false);
+            }
+        };
+        if !moved && !resized {
+            return Ok(false);
+        }
+        let (x, y, w, h) = anchor.area();
+        debug!("anchored capture area moved to {:?}", (x, y, w, h));
+        (self.capture_x0, self.capture_y0) = (x, y);
+        (self.capture_x1, self.capture_y1) = (x + w as i32, y + h as i32);
+        if self.pinned.is_some() {
+            let params = PinParams::from(&self.config.pin);
+            self.pinned = Some(PinnedRegion::new(x, y, w, h, params));
+        }
+        if resized {
+            self.reset_ocr()?;
+            self.draw_capture_area()?;
+        } else {
+            self.redraw_all()?;
+        }
+        Ok(resized)
+    }
+
+    fn export(&self, keys: &[Keycode]) -> bool {
+        same_content(keys, &self.config.keys.export)
+    }
+
+    fn export_last_capture(&self) -> Result<()> {
+        if let Some(capture) = &self.last_capture {
+            let path = save_capture(&self.config.export.directory(), capture, &self.ocr_results)?;
+            info!("Capture and recognition results saved as {:?}", path);
+        }
+        Ok(())
+    }
+
     fn run(&mut self) -> Result<()> {
         let (config_rx, _config_watcher) = watch_config()?;
 
@@ -449,17 +1095,73 @@ impl App {
         let mut decreased = false;
         let mut next_hint_requested = false;
         let mut next_morpheme_requested = false;
+        let mut next_alternative_requested = false;
+        let mut next_senses_requested = false;
+        let mut next_kanji_requested = false;
+        let mut export_requested = false;
+        let mut detect_region_requested = false;
+        let mut hover_toggle_requested = false;
+        let mut pin_toggle_requested = false;
+        let mut anchor_toggle_requested = false;
+
+        // anchored capture area: recognized again once its window is done being resized
+        let resize_settle_time = time::Duration::from_millis(300);
+        let mut resized_at = None;
+
+        // pinned region: its pixels are polled for changes
+        let mut last_poll = time::Instant::now();
+
+        // hover mode: a lookup happens once the cursor rests long enough at a new position
+        let mut dwell_start = time::Instant::now();
+        let mut hovered_pos = None;
+        let mut hover_shown = false;
 
-        let mut window_mapped = false;
         let mut selecting_area = false;
 
         loop {
             let _ = config_rx.try_recv().map(|_| {
                 // empty the event queue so only fresh events are received at the next loop iteration
                 while config_rx.try_recv().is_ok() {}
-                let _ = self.reload_config(window_mapped);
+                let _ = self.reload_config();
             });
 
+            self.swap_loaded()?;
+
+            let mut monitors_changed = false;
+            let mut anchor_changed = false;
+            let mut anchor_destroyed = false;
+            let mut events = std::mem::take(&mut self.skipped_events);
+            while let Some(event) = self.conn.poll_for_event()? {
+                events.push(event);
+            }
+            for event in events {
+                if let Event::RandrScreenChangeNotify(_) | Event::RandrNotify(_) = event {
+                    monitors_changed = true;
+                }
+                if let Some(anchor) = &self.anchor {
+                    anchor_changed |= anchor.concerns(&event);
+                    anchor_destroyed |= anchor.destroyed(&event);
+                }
+            }
+            if monitors_changed {
+                self.refresh_monitors()?;
+            }
+            if anchor_destroyed {
+                debug!("anchor window destroyed");
+                self.release_anchor()?;
+            } else if anchor_changed && self.follow_anchor()? {
+                resized_at = Some(time::Instant::now());
+            }
+            if let Some(at) = resized_at {
+                if at.elapsed() >= resize_settle_time {
+                    resized_at = None;
+                    if self.anchor.is_some() && self.window_mapped {
+                        debug!("anchor window resized, performing OCR");
+                        self.perform_ocr()?;
+                    }
+                }
+            }
+
             let pos = device_state.get_mouse().coords;
             let keys = device_state.get_keys();
 
@@ -468,8 +1170,20 @@ impl App {
                 break;
             }
 
+            if self.export(&keys) {
+                if !export_requested {
+                    debug!("export requested");
+                    if let Err(e) = self.export_last_capture() {
+                        warn!("Failed to export the last capture: {:?}", e);
+                    }
+                }
+                export_requested = true;
+            } else {
+                export_requested = false;
+            }
+
             if self.font_up(&keys) {
-                if window_mapped && !increased {
+                if self.window_mapped && !increased {
                     increased = true;
                     let new_font_scale = (self.font_scale + 25).clamp(50, 200);
                     if new_font_scale != self.font_scale {
@@ -482,7 +1196,7 @@ impl App {
             }
 
             if self.font_down(&keys) {
-                if window_mapped && !decreased {
+                if self.window_mapped && !decreased {
                     decreased = true;
                     let new_font_scale = (self.font_scale - 25).clamp(50, 200);
                     if new_font_scale != self.font_scale {
@@ -499,6 +1213,8 @@ impl App {
                 if !self.ocr_results.is_empty() && !next_hint_requested {
                     self.result_index = (self.result_index + 1) % self.ocr_results.len();
                     self.morpheme_index = 0;
+                    self.sense_page = 0;
+                    self.kanji_index = 0;
                     self.redraw_all()?;
                 }
                 next_hint_requested = true;
@@ -508,9 +1224,14 @@ impl App {
 
             if self.next_morpheme(&keys) {
                 debug!("next morpheme requested");
-                if !self.ocr_results.is_empty() && !next_morpheme_requested {
-                    self.morpheme_index = (self.morpheme_index + 1)
-                        % self.ocr_results[self.result_index].morphemes.len();
+                let morphemes = self
+                    .ocr_results
+                    .get(self.result_index)
+                    .map_or(0, |jpn_text| jpn_text.morphemes.len());
+                if morphemes > 0 && !next_morpheme_requested {
+                    self.morpheme_index = (self.morpheme_index + 1) % morphemes;
+                    self.sense_page = 0;
+                    self.kanji_index = 0;
                     self.redraw_all()?;
                 }
                 next_morpheme_requested = true;
@@ -518,16 +1239,136 @@ impl App {
                 next_morpheme_requested = false;
             }
 
+            if self.next_senses(&keys) {
+                debug!("next senses requested");
+                if !self.ocr_results.is_empty() && !next_senses_requested {
+                    self.sense_page += 1;
+                    self.redraw_all()?;
+                }
+                next_senses_requested = true;
+            } else {
+                next_senses_requested = false;
+            }
+
+            if self.next_kanji(&keys) {
+                debug!("next kanji requested");
+                if !self.ocr_results.is_empty() && !next_kanji_requested {
+                    self.kanji_index += 1;
+                    self.redraw_all()?;
+                }
+                next_kanji_requested = true;
+            } else {
+                next_kanji_requested = false;
+            }
+
+            if self.next_alternative(&keys) {
+                debug!("next alternative requested");
+                if !next_alternative_requested {
+                    self.cycle_alternative()?;
+                }
+                next_alternative_requested = true;
+            } else {
+                next_alternative_requested = false;
+            }
+
+            if self.detect_region(&keys) {
+                if !detect_region_requested && !selecting_area {
+                    debug!("text region detection requested");
+                    self.unpin();
+                    self.release_anchor()?;
+                    self.reset_ocr()?;
+                    hover_shown = false;
+                    self.unmap_overlay()?;
+                    if let Some(block) = self.detect_text_region(pos)? {
+                        self.map_overlay()?;
+                        self.draw_capture_area()?;
+                        debug!("performing OCR");
+                        self.ocr_capture(block)?;
+                        self.draw_highlights()?;
+                        self.draw_hint()?;
+                    }
+                    // the region's hint stays until the cursor rests somewhere else
+                    hovered_pos = Some(pos);
+                }
+                detect_region_requested = true;
+            } else {
+                detect_region_requested = false;
+            }
+
+            if self.hover_toggle(&keys) {
+                if !hover_toggle_requested {
+                    self.hover_enabled = !self.hover_enabled;
+                    info!(
+                        "Hover mode {}",
+                        if self.hover_enabled { "on" } else { "off" }
+                    );
+                    hovered_pos = None;
+                }
+                hover_toggle_requested = true;
+            } else {
+                hover_toggle_requested = false;
+            }
+
+            if self.pin_toggle(&keys) {
+                if !pin_toggle_requested && !selecting_area {
+                    self.toggle_pin();
+                }
+                pin_toggle_requested = true;
+            } else {
+                pin_toggle_requested = false;
+            }
+
+            if self.anchor_toggle(&keys) {
+                if !anchor_toggle_requested && !selecting_area {
+                    self.toggle_anchor()?;
+                }
+                anchor_toggle_requested = true;
+            } else {
+                anchor_toggle_requested = false;
+            }
+
+            let poll_interval = time::Duration::from_millis(self.config.pin.poll_interval);
+            if self.pinned.is_some() && !selecting_area && last_poll.elapsed() >= poll_interval {
+                trace!("polling pinned region");
+                last_poll = time::Instant::now();
+                self.watch_pinned()?;
+            }
+
+            if pos != mouse_pos {
+                dwell_start = time::Instant::now();
+            }
+
+            let hovering = self.hover_enabled || self.hover_modifier(&keys);
+            let dwell_time = time::Duration::from_millis(self.config.hover.dwell_time);
+            if hovering
+                && !selecting_area
+                && self.pinned.is_none()
+                && self.anchor.is_none()
+                && hovered_pos != Some(pos)
+                && dwell_start.elapsed() >= dwell_time
+            {
+                debug!("hover lookup");
+                hovered_pos = Some(pos);
+                self.reset_ocr()?;
+                self.unmap_overlay()?;
+                hover_shown = self.hover_lookup(pos)?;
+                if hover_shown {
+                    self.map_overlay()?;
+                    self.draw_hint()?;
+                }
+            } else if !hovering && hover_shown {
+                debug!("hiding hover hint");
+                self.reset_ocr()?;
+                self.unmap_overlay()?;
+                hover_shown = false;
+                hovered_pos = None;
+            }
+
             if self.trigger(&keys) {
                 trace!("trigger keys down");
                 if selecting_area {
                     if pos != mouse_pos {
-                        if !window_mapped {
-                            debug!("mapping overlay");
-                            self.conn.map_window(self.window)?;
-                            raise(&self.conn, self.window)?;
-                            window_mapped = true;
-                        }
+                        self.map_overlay()?;
                         trace!("adjusting capture area");
                         (self.capture_x1, self.capture_y1) = pos;
                         self.clear_overlay()?;
@@ -535,15 +1376,14 @@ impl App {
                     }
                 } else {
                     debug!("starting capture area selection");
+                    self.unpin();
+                    self.release_anchor()?;
                     selecting_area = true;
+                    hover_shown = false;
                     (self.capture_x0, self.capture_y0) = pos;
                     (self.capture_x1, self.capture_y1) = pos;
                     self.reset_ocr()?;
-                    if window_mapped {
-                        debug!("hiding overlay");
-                        self.conn.unmap_window(self.window)?;
-                        window_mapped = false;
-                    }
+                    self.unmap_overlay()?;
                 }
             } else if selecting_area {
                 debug!("stopping capture area selection");
@@ -553,6 +1393,8 @@ impl App {
                     debug!("performing OCR");
                     self.perform_ocr()?;
                 }
+                // the capture's hint stays until the cursor rests somewhere else
+                hovered_pos = Some(pos);
             }
 
             mouse_pos = pos;
@@ -572,33 +1414,91 @@ fn main() -> Result<()> {
 
     let morph_api = JpnMorphAnalysisAPI::with_lindera_address(&config.lindera.server_address)?;
 
+    let mut ocr = JpnOCR::new(morph_api);
+    ocr.set_script_policy(ScriptPolicy::from(&config.script));
+    ocr.set_layout_params(LayoutParams::from(&config.layout));
+
+    match Cli::parse().command {
+        Some(Command::Files { output, images }) => {
+            let font_path = get_font_path(&config);
+            let mut offline = OfflineOCR::new(ocr, config, font_path)?;
+            return offline.files(&images, output.as_deref());
+        }
+        Some(Command::Watch { output, directory }) => {
+            let font_path = get_font_path(&config);
+            let mut offline = OfflineOCR::new(ocr, config, font_path)?;
+            return offline.watch(&directory, output.as_deref());
+        }
+        None => {}
+    }
+
     let (conn, screen_num) = x11rb::connect(None)?;
     xfixes_init(&conn);
     let screen = &conn.setup().roots[screen_num];
-    let screen_w = screen.width_in_pixels;
-    let screen_h = screen.height_in_pixels;
+    let root = screen.root;
+    if let Err(e) = randr_init(&conn, root) {
+        warn!(
+            "RandR 1.5 unavailable, the screen is taken as a single monitor: {:?}",
+            e
+        );
+    }
+    let monitors = monitors(&conn, root)?;
+    debug!("monitors: {:?}", monitors);
+    let (screen_w, screen_h) = extent(&monitors);
+
+    let screen_capture = ScreenCapture::new(&conn, screen_num)?;
+
+    // the dictionary's index is built ahead of the first hint
+    std::thread::spawn(Dictionary::get);
 
-    let window = create_overlay_fullscreen_window(&conn, screen)?;
+    let window = create_overlay_window(&conn, screen, 0, 0, screen_w, screen_h)?;
     with_name(&conn, window, "kanjisabi")?;
 
+    let kanjidic = config.kanji.kanjidic();
+    let jmnedict = config.names.jmnedict();
+    let yomitan = config.yomitan.dictionaries.clone();
+    let (loaded_tx, loaded_rx) = channel();
+
+    let hover_enabled = config.hover.enabled;
     let mut app = App {
         conn,
         sdl2_ttf_ctx: sdl2::ttf::init()?,
         font_path: get_font_path(&config),
+        ocr,
+        kanjidic: None,
+        loaded_tx,
+        loaded_rx,
+        radicals: load_radicals(&config),
+        dictionaries: Dictionaries::default(),
+        cache: OCRCache::new(config.cache.capacity),
+        screen_capture,
         config,
+        root,
+        window,
+        monitors,
         screen_w,
         screen_h,
-        ocr: JpnOCR::new(morph_api),
-        window,
         capture_x0: 0,
         capture_y0: 0,
         capture_x1: 0,
         capture_y1: 0,
         ocr_results: vec![],
+        last_capture: None,
         result_index: 0,
         morpheme_index: 0,
+        sense_page: 0,
+        kanji_index: 0,
         font_scale: 100,
+        hover_enabled,
+        window_mapped: false,
+        pinned: None,
+        anchor: None,
+        skipped_events: vec![],
     };
 
+    app.load_in_background(move || Loaded::Kanjidic(load_kanjidic(kanjidic)));
+    app.load_in_background(move || Loaded::Jmnedict(load_jmnedict(jmnedict)));
+    app.load_in_background(move || Loaded::Yomitan(load_yomitan(&yomitan)));
+
     app.run()
 }
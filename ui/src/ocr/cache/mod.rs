@@ -0,0 +1,120 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+
+use super::jpn::JpnText;
+
+/// identifies a capture: its (preprocessed) pixels and the settings that affect its recognition
+pub fn cache_key(frame_data: &[u8], width: u32, height: u32, settings: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    frame_data.hash(&mut hasher);
+    width.hash(&mut hasher);
+    height.hash(&mut hasher);
+    settings.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// least recently used cache of recognition results
+pub struct OCRCache {
+    capacity: usize,
+    entries: VecDeque<(u64, Vec<JpnText>)>,
+}
+
+impl OCRCache {
+    pub fn new(capacity: usize) -> Self {
+        OCRCache {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn get(&mut self, key: u64) -> Option<&Vec<JpnText>> {
+        let index = self.entries.iter().position(|(k, _)| *k == key)?;
+        let entry = self.entries.remove(index)?;
+        self.entries.push_front(entry);
+        self.entries.front().map(|(_, results)| results)
+    }
+
+    pub fn insert(&mut self, key: u64, results: Vec<JpnText>) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.entries.retain(|(k, _)| *k != key);
+        self.entries.truncate(self.capacity - 1);
+        self.entries.push_front((key, results));
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.entries.truncate(capacity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// results told apart by their position
+    fn results(x: i32) -> Vec<JpnText> {
+        vec![JpnText {
+            morphemes: vec![],
+            chars: vec![],
+            lines: vec![],
+            ruby: vec![],
+            x,
+            y: 0,
+            w: 0,
+            h: 0,
+        }]
+    }
+
+    fn cached(cache: &mut OCRCache, key: u64) -> Option<i32> {
+        cache.get(key).map(|results| results[0].x)
+    }
+
+    #[test]
+    fn hits_and_misses() {
+        let mut cache = OCRCache::new(2);
+        assert_eq!(cached(&mut cache, 1), None);
+        cache.insert(1, results(10));
+        assert_eq!(cached(&mut cache, 1), Some(10));
+        assert_eq!(cached(&mut cache, 2), None);
+        // inserting again replaces the results
+        cache.insert(1, results(11));
+        assert_eq!(cached(&mut cache, 1), Some(11));
+
+        let mut disabled = OCRCache::new(0);
+        disabled.insert(1, results(10));
+        assert_eq!(cached(&mut disabled, 1), None);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = OCRCache::new(2);
+        cache.insert(1, results(10));
+        cache.insert(2, results(20));
+        // using 1 makes 2 the least recently used
+        assert_eq!(cached(&mut cache, 1), Some(10));
+        cache.insert(3, results(30));
+        assert_eq!(cached(&mut cache, 2), None);
+        assert_eq!(cached(&mut cache, 1), Some(10));
+        assert_eq!(cached(&mut cache, 3), Some(30));
+
+        // 1 is now the least recently used
+        cache.set_capacity(1);
+        assert_eq!(cached(&mut cache, 1), None);
+        assert_eq!(cached(&mut cache, 3), Some(30));
+    }
+
+    #[test]
+    fn keys_depend_on_pixels_size_and_settings() {
+        let pixels = [0u8, 64, 128, 255];
+        let settings = (100f32.to_bits(), true);
+        let key = cache_key(&pixels, 2, 2, settings);
+        assert_eq!(key, cache_key(&pixels, 2, 2, settings));
+        assert_ne!(key, cache_key(&[0, 64, 128, 254], 2, 2, settings));
+        assert_ne!(key, cache_key(&pixels, 4, 1, settings));
+        assert_ne!(key, cache_key(&pixels, 2, 2, (50f32.to_bits(), true)));
+        assert_ne!(key, cache_key(&pixels, 2, 2, (100f32.to_bits(), false)));
+    }
+}
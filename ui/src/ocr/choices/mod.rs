@@ -0,0 +1,180 @@
+//! Recognition through Tesseract's C API, as the `tesseract` crate neither exposes its result & choice
+//! iterators nor keeps an initialized instance around for the next recognition.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use anyhow::{anyhow, Result};
+use tesseract_sys::{
+    TessBaseAPI, TessBaseAPICreate, TessBaseAPIDelete, TessBaseAPIEnd, TessBaseAPIGetIterator,
+    TessBaseAPIGetTsvText, TessBaseAPIInit3, TessBaseAPIRecognize, TessBaseAPISetImage,
+    TessBaseAPISetVariable, TessChoiceIteratorConfidence, TessChoiceIteratorDelete,
+    TessChoiceIteratorGetUTF8Text, TessChoiceIteratorNext, TessDeleteText,
+    TessPageIteratorBoundingBox, TessPageIteratorLevel_RIL_SYMBOL, TessResultIterator,
+    TessResultIteratorConfidence, TessResultIteratorDelete, TessResultIteratorGetChoiceIterator,
+    TessResultIteratorGetPageIterator, TessResultIteratorGetUTF8Text, TessResultIteratorNext,
+};
+
+/// A candidate reading of a single symbol, as scored by Tesseract
+#[derive(Debug, Clone, PartialEq)]
+pub struct OCRChoice {
+    pub text: String,
+    pub conf: f32,
+}
+
+/// A single recognized symbol, along with all the candidates Tesseract considered for it
+#[derive(Debug)]
+pub struct OCRSymbol {
+    pub text: String,
+    pub conf: f32,
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+    pub choices: Vec<OCRChoice>,
+}
+
+/// a Tesseract instance, initialized once as loading its models takes a while; a recognition's results
+/// stay available until the next one
+pub struct BaseApi(*mut TessBaseAPI);
+
+impl Drop for BaseApi {
+    fn drop(&mut self) {
+        unsafe {
+            TessBaseAPIEnd(self.0);
+            TessBaseAPIDelete(self.0);
+        }
+    }
+}
+
+struct ResultIterator(*mut TessResultIterator);
+
+impl Drop for ResultIterator {
+    fn drop(&mut self) {
+        unsafe { TessResultIteratorDelete(self.0) }
+    }
+}
+
+/// copies then frees a string allocated by Tesseract
+unsafe fn owned_text(text: *mut c_char) -> Option<String> {
+    if text.is_null() {
+        return None;
+    }
+    let s = CStr::from_ptr(text).to_string_lossy().into_owned();
+    TessDeleteText(text);
+    Some(s)
+}
+
+impl BaseApi {
+    pub fn new(lang: &str) -> Result<BaseApi> {
+        let lang = CString::new(lang)?;
+        // best choices per symbol, rather than the raw per-timestep LSTM outputs
+        let choice_mode = (CString::new("lstm_choice_mode")?, CString::new("2")?);
+
+        unsafe {
+            let api = BaseApi(TessBaseAPICreate());
+            if TessBaseAPIInit3(api.0, ptr::null(), lang.as_ptr()) != 0 {
+                return Err(anyhow!("failed to initialize Tesseract"));
+            }
+            TessBaseAPISetVariable(api.0, choice_mode.0.as_ptr(), choice_mode.1.as_ptr());
+            Ok(api)
+        }
+    }
+
+    pub fn recognize(
+        &mut self,
+        frame_data: &[u8],
+        width: i32,
+        height: i32,
+        bytes_per_pixel: i32,
+        bytes_per_line: i32,
+    ) -> Result<()> {
+        if frame_data.len() < (height * bytes_per_line) as usize {
+            return Err(anyhow!("frame data too small for the given dimensions"));
+        }
+
+        unsafe {
+            TessBaseAPISetImage(
+                self.0,
+                frame_data.as_ptr(),
+                width,
+                height,
+                bytes_per_pixel,
+                bytes_per_line,
+            );
+            if TessBaseAPIRecognize(self.0, ptr::null_mut()) != 0 {
+                return Err(anyhow!("Tesseract recognition failed"));
+            }
+        }
+        Ok(())
+    }
+
+    /// the last recognition's results in Tesseract's TSV format
+    pub fn tsv_text(&self) -> Result<String> {
+        unsafe { owned_text(TessBaseAPIGetTsvText(self.0, 0)) }
+            .ok_or_else(|| anyhow!("no Tesseract results"))
+    }
+
+    /// the symbols of the last recognition, with the candidates Tesseract considered for each
+    pub fn symbols(&self) -> Vec<OCRSymbol> {
+        unsafe {
+            let it = TessBaseAPIGetIterator(self.0);
+            if it.is_null() {
+                return vec![];
+            }
+            let it = ResultIterator(it);
+            let level = TessPageIteratorLevel_RIL_SYMBOL;
+
+            let mut symbols = vec![];
+            loop {
+                if let Some(text) = owned_text(TessResultIteratorGetUTF8Text(it.0, level)) {
+                    let (mut left, mut top, mut right, mut bottom): (c_int, c_int, c_int, c_int) =
+                        (0, 0, 0, 0);
+                    TessPageIteratorBoundingBox(
+                        TessResultIteratorGetPageIterator(it.0),
+                        level,
+                        &mut left,
+                        &mut top,
+                        &mut right,
+                        &mut bottom,
+                    );
+
+                    let mut choices = vec![];
+                    let ci = TessResultIteratorGetChoiceIterator(it.0);
+                    if !ci.is_null() {
+                        loop {
+                            let choice = TessChoiceIteratorGetUTF8Text(ci);
+                            if !choice.is_null() {
+                                choices.push(OCRChoice {
+                                    text: CStr::from_ptr(choice).to_string_lossy().into_owned(),
+                                    conf: TessChoiceIteratorConfidence(ci),
+                                });
+                            }
+                            if TessChoiceIteratorNext(ci) == 0 {
+                                break;
+                            }
+                        }
+                        TessChoiceIteratorDelete(ci);
+                    }
+
+                    symbols.push(OCRSymbol {
+                        text,
+                        conf: TessResultIteratorConfidence(it.0, level),
+                        x: left,
+                        y: top,
+                        w: right - left,
+                        h: bottom - top,
+                        choices,
+                    });
+                }
+
+                if TessResultIteratorNext(it.0, level) == 0 {
+                    break;
+                }
+            }
+
+            symbols
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use super::jpn::JpnText;
+
+/// the recognized text, one line per text found, as the morphological analysis got it
+pub fn recognized_text(jpn_texts: &[JpnText]) -> String {
+    jpn_texts
+        .iter()
+        .map(JpnText::text)
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// edit distance between the expected and actual texts, relative to the length of the expected text
+///
+/// whitespace is ignored, as line breaks and word separators depend on the layout more than on the OCR
+pub fn char_error_rate(expected: &str, actual: &str) -> f32 {
+    let expected: Vec<char> = expected.chars().filter(|c| !c.is_whitespace()).collect();
+    let actual: Vec<char> = actual.chars().filter(|c| !c.is_whitespace()).collect();
+    if expected.is_empty() {
+        return if actual.is_empty() { 0. } else { 1. };
+    }
+    levenshtein(&expected, &actual) as f32 / expected.len() as f32
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_texts() {
+        assert_eq!(char_error_rate("", ""), 0.);
+        assert_eq!(char_error_rate("", "漢字"), 1.);
+        assert_eq!(char_error_rate("漢字", ""), 1.);
+        assert_eq!(char_error_rate(" \n", "\t"), 0.);
+    }
+
+    #[test]
+    fn edits() {
+        assert_eq!(char_error_rate("abcd", "abcd"), 0.);
+        assert_eq!(char_error_rate("abcd", "abxcd"), 0.25);
+        assert_eq!(char_error_rate("abcd", "abd"), 0.25);
+        assert_eq!(char_error_rate("abcd", "abxd"), 0.25);
+        assert_eq!(char_error_rate("abcd", "dcba"), 1.);
+    }
+
+    #[test]
+    fn multibyte_characters() {
+        // one substitution over four characters, not over their twelve UTF-8 bytes
+        assert_eq!(char_error_rate("日本語だ", "日本話だ"), 0.25);
+        assert_eq!(char_error_rate("日本語だ", "日本語だよ"), 0.25);
+        assert_eq!(char_error_rate("日本 語\nだ", "日本語だ"), 0.);
+    }
+}
@@ -1,83 +1,169 @@
 use std::collections::BTreeMap;
+use std::ops::Range;
 
+use super::layout::{is_ruby_of, lines, paragraphs, split_ruby, LayoutParams, OCRLine};
 use super::{OCRWord, OCR};
+use crate::script::{is_japanese, to_katakana, ScriptPolicy};
 
 use anyhow::Result;
-use jmdict::{Entry, Gloss};
-use log::info;
+use log::{info, warn};
 use morph::{JpnMorphAnalysisAPI, Morpheme};
 use tokio::runtime::{Builder, Runtime};
 
 pub struct JpnOCR {
     ocr: OCR,
+    /// words recognized with a confidence at or below this get alternative candidates for their characters
     threshold: f32,
-    discriminator: fn(&str) -> bool,
+    script_policy: ScriptPolicy,
+    layout_params: LayoutParams,
     morph_api: JpnMorphAnalysisAPI,
     rt: Runtime,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct VisualMorpheme {
     pub morpheme: Morpheme,
     pub bbox: Option<(i32, i32, i32, i32)>,
+    pub reading_hint: Option<ReadingHint>,
 }
 
-#[derive(Debug)]
+/// furigana printed over a morpheme
+#[derive(Debug, Clone)]
+pub struct ReadingHint {
+    pub text: String,
+    /// whether the reading from the morphological analysis is consistent with the furigana
+    pub agrees: bool,
+}
+
+/// furigana found by the OCR, kept apart from the main text
+#[derive(Debug, Clone)]
+pub struct Ruby {
+    pub text: String,
+    pub bbox: (i32, i32, i32, i32),
+}
+
+/// a recognized character, along with the other candidates Tesseract considered for it, best first
+#[derive(Debug, Clone)]
+pub struct JpnChar {
+    pub candidates: Vec<char>,
+    pub selected: usize,
+    pub conf: f32,
+    /// only the first character of each OCR word has one
+    pub bbox: Option<(i32, i32, i32, i32)>,
+}
+
+impl JpnChar {
+    pub fn char(&self) -> char {
+        self.candidates[self.selected]
+    }
+
+    pub fn has_alternatives(&self) -> bool {
+        self.candidates.len() > 1
+    }
+
+    pub fn next_candidate(&mut self) {
+        self.selected = (self.selected + 1) % self.candidates.len();
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct JpnText {
     pub morphemes: Vec<VisualMorpheme>,
+    pub chars: Vec<JpnChar>,
+    /// bounding boxes of the OCR lines the text spans over
+    pub lines: Vec<(i32, i32, i32, i32)>,
+    /// furigana over the text, in reading order
+    pub ruby: Vec<Ruby>,
+    /// bounding box of the first line, where hints are anchored
     pub x: i32,
     pub y: i32,
     pub w: i32,
     pub h: i32,
 }
 
-fn is_kanji(c: char) -> bool {
-    ('\u{4e00}'..='\u{9ffc}').contains(&c)         // https://www.unicode.org/charts/PDF/U4E00.pdf
-        || ('\u{f900}'..='\u{faff}').contains(&c)  // https://www.unicode.org/charts/PDF/UF900.pdf
-        || ('\u{3400}'..='\u{4dbf}').contains(&c)  // https://www.unicode.org/charts/PDF/U3400.pdf
-        || ('\u{20000}'..='\u{2a6dd}').contains(&c)// https://www.unicode.org/charts/PDF/U3400.pdf
-        || ('\u{2a700}'..='\u{2b734}').contains(&c)// https://www.unicode.org/charts/PDF/U2A700.pdf
-        || ('\u{2b740}'..='\u{2b81d}').contains(&c)// https://www.unicode.org/charts/PDF/U2B740.pdf
-        || ('\u{2b820}'..='\u{2cea1}').contains(&c)// https://www.unicode.org/charts/PDF/U2B820.pdf
-        || ('\u{2ceb0}'..='\u{2ebe0}').contains(&c)// https://www.unicode.org/charts/PDF/U2CEB0.pdf
-        || ('\u{2f800}'..='\u{2fa1d}').contains(&c)// https://www.unicode.org/charts/PDF/U2F800.pdf
-        || ('\u{30000}'..='\u{3134a}').contains(&c)// https://www.unicode.org/charts/PDF/U30000.pdf
-        || c == '\u{3005}' // 々 - https://www.unicode.org/charts/PDF/U3000.pdf
-}
+impl JpnText {
+    pub fn text(&self) -> String {
+        self.chars.iter().map(JpnChar::char).collect()
+    }
 
-fn is_hiragana(c: char) -> bool {
-    ('\u{3041}'..='\u{3096}').contains(&c)          // https://www.unicode.org/charts/PDF/U3040.pdf
-        || c == '\u{1b001}'                              // https://www.unicode.org/charts/PDF/U1B000.pdf
-        || c == '\u{1b11f}'                              // https://www.unicode.org/charts/PDF/U1B100.pdf
-        || ('\u{1b150}'..='\u{1b152}').contains(&c) // https://www.unicode.org/charts/PDF/U1B130.pdf
-}
+    /// indices of the characters making up the morpheme at `morpheme_index`
+    pub fn char_range(&self, morpheme_index: usize) -> Range<usize> {
+        let start = self
+            .morphemes
+            .iter()
+            .take(morpheme_index)
+            .map(|vm| vm.morpheme.text.chars().count())
+            .sum();
+        let len = self
+            .morphemes
+            .get(morpheme_index)
+            .map(|vm| vm.morpheme.text.chars().count())
+            .unwrap_or_default();
+        start..start + len
+    }
+
+    /// transform all the bounding boxes of the text, e.g. to map them back onto the original capture
+    pub fn map_boxes(&mut self, f: impl Fn((i32, i32, i32, i32)) -> (i32, i32, i32, i32)) {
+        for line in self.lines.iter_mut() {
+            *line = f(*line);
+        }
+        for ruby in self.ruby.iter_mut() {
+            ruby.bbox = f(ruby.bbox);
+        }
+        for c in self.chars.iter_mut() {
+            c.bbox = c.bbox.map(&f);
+        }
+        for vm in self.morphemes.iter_mut() {
+            vm.bbox = vm.bbox.map(&f);
+        }
+        (self.x, self.y, self.w, self.h) = f((self.x, self.y, self.w, self.h));
+    }
 
-fn is_katakana(c: char) -> bool {
-    ('\u{30a1}'..='\u{30fa}').contains(&c)|| c == '\u{30fc}' // https://www.unicode.org/charts/PDF/U30A0.pdf
-        || ('\u{31f0}'..='\u{31ff}').contains(&c)            // https://www.unicode.org/charts/PDF/U31F0.pdf
-        || ('\u{ff66}'..='\u{ff9d}').contains(&c)            // https://www.unicode.org/charts/PDF/UFF00.pdf
-        || c == '\u{1b000}'                                       // https://www.unicode.org/charts/PDF/U1B000.pdf
-        || ('\u{1b164}'..='\u{1b167}').contains(&c) // https://www.unicode.org/charts/PDF/U1B130.pdf
+    /// index of the morpheme at the point `(x, y)`, on the line containing the point: the last one starting left of
+    /// the point, among the morphemes having a bounding box of their own
+    pub fn morpheme_at(&self, x: i32, y: i32) -> Option<usize> {
+        let &(_, ly, _, lh) = self
+            .lines
+            .iter()
+            .find(|&&(lx, ly, lw, lh)| lx <= x && x <= lx + lw && ly <= y && y <= ly + lh)?;
+        self.morphemes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, vm)| vm.bbox.filter(|bbox| bbox.2 > 0).map(|bbox| (i, bbox)))
+            .filter(|&(_, (bx, by, _, bh))| bx <= x && ly <= by + bh / 2 && by + bh / 2 <= ly + lh)
+            .max_by_key(|&(_, (bx, _, _, _))| bx)
+            .map(|(i, _)| i)
+    }
+
+    /// the least confident character having alternatives, within the morpheme at `morpheme_index`
+    pub fn uncertain_char(&self, morpheme_index: usize) -> Option<usize> {
+        self.char_range(morpheme_index)
+            .filter(|&i| self.chars[i].has_alternatives())
+            .min_by(|&i, &j| self.chars[i].conf.total_cmp(&self.chars[j].conf))
+    }
 }
 
 impl JpnOCR {
     pub fn new(morph_api: JpnMorphAnalysisAPI) -> JpnOCR {
         JpnOCR {
             // TODO try to support 'jpn_vert' too; initial tries gave very bad results
-            ocr: OCR {
-                lang: String::from("jpn"),
-            },
+            ocr: OCR::new("jpn"),
             threshold: 80.,
-            discriminator: |s| {
-                // assumption: OCR does not group non-Japanese and Japanese characters (e.g. ２階), or it's ok not to care about them
-                s.chars()
-                    .all(|c| is_kanji(c) || is_katakana(c) || is_hiragana(c))
-            },
+            script_policy: ScriptPolicy::default(),
+            layout_params: LayoutParams::default(),
             morph_api,
             rt: Builder::new_multi_thread().enable_all().build().unwrap(),
         }
     }
 
+    pub fn set_script_policy(&mut self, script_policy: ScriptPolicy) {
+        self.script_policy = script_policy;
+    }
+
+    pub fn set_layout_params(&mut self, layout_params: LayoutParams) {
+        self.layout_params = layout_params;
+    }
+
     pub fn recognize(
         &mut self,
         frame_data: &[u8],
@@ -86,74 +172,122 @@ impl JpnOCR {
         bytes_per_pixel: i32,
         bytes_per_line: i32,
     ) -> Result<Vec<JpnText>> {
-        let ocr_words =
+        let mut ocr_words =
             self.ocr
                 .recognize_words(frame_data, width, height, bytes_per_pixel, bytes_per_line)?;
+
+        let threshold = self.threshold;
+        if ocr_words.iter().any(|w| w.conf <= threshold) {
+            // the candidates of each symbol, only walked through when there is something doubtful
+            let symbols = self.ocr.recognized_symbols();
+            OCR::attach_alternatives(&mut ocr_words, &symbols, |w| w.conf <= threshold);
+        }
+
         Ok(self.ocr_words_to_text(&ocr_words))
     }
 
+    /// switch the character at `char_index` to its next candidate, then analyze the corrected text again;
+    /// the previous morphemes are kept when the analysis fails
+    pub fn cycle_alternative(&mut self, jpn_text: &mut JpnText, char_index: usize) {
+        if let Some(c) = jpn_text.chars.get_mut(char_index) {
+            c.next_candidate();
+            let morphemes = self.analyze(&jpn_text.chars, &jpn_text.ruby);
+            if morphemes.is_empty() {
+                warn!("Failed to analyze the corrected text: {}", jpn_text.text());
+            } else {
+                jpn_text.morphemes = morphemes;
+            }
+        }
+    }
+
     fn ocr_words_to_text(&mut self, words: &[OCRWord]) -> Vec<JpnText> {
-        words
+        let (words, ruby) = split_ruby(words, &self.layout_params);
+        let mut ruby: Vec<Ruby> = ruby
+            .into_iter()
+            .map(|w| Ruby {
+                text: w.text.clone(),
+                bbox: (w.x, w.y, w.w, w.h),
+            })
+            .collect();
+        ruby.sort_by_key(|r| (r.bbox.1, r.bbox.0));
+
+        paragraphs(lines(&words), &self.layout_params)
             .iter()
-            .fold(
-                BTreeMap::new(),
-                |mut acc: BTreeMap<(u32, u32, u32, u32), Vec<&OCRWord>>, word| {
-                    acc.entry(word.line_id).or_default().push(word);
-                    acc
-                },
-            )
-            .values_mut()
-            .flat_map(|line| self.line_to_text(line))
+            .flat_map(|paragraph| self.paragraph_to_text(paragraph, &ruby))
             .collect()
     }
 
-    /// digest OCR'd Japanese characters belonging to the same OCR 'line' into tentative words
-    fn line_to_text(&mut self, line: &[&OCRWord]) -> Vec<JpnText> {
-        let threshold = self.threshold;
-        let discriminator = self.discriminator;
-        let is_valid_jpn = |w: &&OCRWord| w.conf <= threshold || !(discriminator)(&w.text);
-        let to_jpn = |seq: &[&OCRWord]| {
-            if seq.is_empty() {
+    /// digest OCR'd Japanese characters belonging to the same paragraph into tentative words
+    fn paragraph_to_text(&mut self, paragraph: &[OCRLine], ruby: &[Ruby]) -> Vec<JpnText> {
+        // words tagged with the index of their line within the paragraph
+        let words: Vec<(usize, &OCRWord)> = paragraph
+            .iter()
+            .enumerate()
+            .flat_map(|(i, line)| line.words.iter().map(move |w| (i, *w)))
+            .collect();
+        let script_policy = self.script_policy;
+        let is_valid_jpn = |(_, w): &(usize, &OCRWord)| !script_policy.accepts(&w.text);
+        let to_jpn = |seq: &[(usize, &OCRWord)]| {
+            // sequences of only digits, punctuation, etc. are not worth analyzing
+            if !seq.iter().any(|(_, w)| w.text.chars().any(is_japanese)) {
                 None
             } else {
-                Some(self.word_seq_to_text(seq))
+                Some(self.word_seq_to_text(seq, ruby))
             }
         };
-        line.split(is_valid_jpn).filter_map(to_jpn).collect()
+        words.split(is_valid_jpn).filter_map(to_jpn).collect()
     }
 
-    fn word_seq_to_text(&mut self, seq: &[&OCRWord]) -> JpnText {
-        let chars_in_seq = seq
-            .iter()
-            .map(|t| t.text.chars().count() as u32)
-            .sum::<u32>();
+    fn word_seq_to_text(&mut self, seq: &[(usize, &OCRWord)], ruby: &[Ruby]) -> JpnText {
+        // each character of all words of the sequence gets a bounding box if it's the first character of its word
+        // later used for assigning bounding boxes to morphemes
+        let mut chars = vec![];
+        for (_, word) in seq {
+            for (i, c) in word.text.chars().enumerate() {
+                chars.push(to_jpn_char(word, i, c));
+            }
+        }
 
-        let mut x = std::i32::MAX;
-        let mut y = 0;
-        let mut w = 0;
-        let mut h = 0;
-        let mut text = "".to_owned();
+        let lines: Vec<(i32, i32, i32, i32)> = seq
+            .iter()
+            .fold(
+                BTreeMap::new(),
+                |mut acc: BTreeMap<usize, Vec<&OCRWord>>, (line, word)| {
+                    acc.entry(*line).or_default().push(word);
+                    acc
+                },
+            )
+            .values()
+            .map(|line| line_bbox(line))
+            .collect();
+        let (x, y, w, h) = lines[0];
 
-        // for each character in all words of the sequence, assign it a bounding box if it's the first character of its word
-        // later used for assigning bounding boxes to morphemes
-        let mut bounding_boxes = vec![];
+        let ruby: Vec<Ruby> = ruby
+            .iter()
+            .filter(|r| {
+                seq.iter()
+                    .any(|(_, w)| is_ruby_of(r.bbox, (w.x, w.y, w.w, w.h)))
+            })
+            .cloned()
+            .collect();
 
-        // averaging out ys and hs, as Tesseract bboxes are not accurate
-        // see https://github.com/tesseract-ocr/tesseract/labels/bounding%20box
-        for word in seq {
-            bounding_boxes.push(Some((word.x, word.y, word.w, word.h)));
+        let morphemes = self.analyze(&chars, &ruby);
 
-            for _ in 1..word.text.chars().count() {
-                bounding_boxes.push(None);
-            }
-            x = std::cmp::min(x, word.x);
-            y += word.y;
-            w = std::cmp::max(w, word.w + word.x - x);
-            h += word.h;
-            text.push_str(&word.text);
+        JpnText {
+            morphemes,
+            chars,
+            lines,
+            ruby,
+            x,
+            y,
+            w,
+            h,
         }
-        y = (y as f32 / chars_in_seq as f32) as i32;
-        h = (h as f32 / chars_in_seq as f32) as i32;
+    }
+
+    /// morphological analysis of the currently selected candidates, bounding boxes assigned to the resulting morphemes
+    fn analyze(&mut self, chars: &[JpnChar], ruby: &[Ruby]) -> Vec<VisualMorpheme> {
+        let text: String = chars.iter().map(JpnChar::char).collect();
 
         let morphemes = self
             .rt
@@ -162,18 +296,12 @@ impl JpnOCR {
 
         let chars_in_morphemes = morphemes
             .iter()
-            .map(|m| m.text.chars().count() as u32)
-            .sum::<u32>();
+            .map(|m| m.text.chars().count())
+            .sum::<usize>();
 
-        if chars_in_seq != chars_in_morphemes {
+        if chars.len() != chars_in_morphemes {
             info!("Inconsistent morphological analysis results, discarding them");
-            return JpnText {
-                morphemes: vec![],
-                x,
-                y,
-                w,
-                h,
-            };
+            return vec![];
         }
 
         let mut v_morphemes = vec![];
@@ -185,8 +313,8 @@ impl JpnOCR {
             let mut y = std::i32::MAX;
             let mut w = 0;
             let mut h = 0;
-            for i in 0..len {
-                if let Some((bx, by, bw, bh)) = bounding_boxes[char_index + i] {
+            for c in &chars[char_index..char_index + len] {
+                if let Some((bx, by, bw, bh)) = c.bbox {
                     x = std::cmp::min(x, bx);
                     y = std::cmp::min(y, by);
                     w = std::cmp::max(w, bw + bx - x);
@@ -198,71 +326,102 @@ impl JpnOCR {
             let v_morpheme = VisualMorpheme {
                 morpheme,
                 bbox: Some(bbox),
+                reading_hint: None,
             };
             char_index += len;
 
-            // TODO: dictionary integration
-            // if let Some(dict_form) = morpheme.detail.get(6) {
-            //     if dict_form != &morpheme.text {
-            //         println!("morpheme dict form: {}", dict_form);
-            //         print_jmdict_results(&dict_form);
-            //     }
-            // }
-
             v_morphemes.push(v_morpheme);
         }
 
-        JpnText {
-            morphemes: v_morphemes,
-            x,
-            y,
-            w,
-            h,
-        }
+        attach_reading_hints(&mut v_morphemes, ruby);
+
+        v_morphemes
     }
 }
 
-pub fn print_jmdict_results(text: &str) {
-    // TODO use PoS: e.senses().any(|s| s.parts_of_speech().any(morpheme_accepts_jmdict_pos))
-    // see https://docs.rs/jmdict/latest/jmdict/enum.PartOfSpeech.html
-    // TODO filter out senses with Info::ObsoleteTerm
-    match jmdict::entries().find(|e| e.kanji_elements().any(|k| k.text == text)) {
-        Some(entry) => print_entry(&entry),
-        None => {
-            if let Some(entry) =
-                jmdict::entries().find(|e| e.reading_elements().any(|k| k.text == text))
-            {
-                print_entry(&entry)
-            }
+/// each furigana goes to the morpheme below it, sharing the most horizontal space with it
+fn attach_reading_hints(morphemes: &mut [VisualMorpheme], ruby: &[Ruby]) {
+    for r in ruby {
+        let (rx, _, rw, _) = r.bbox;
+        let below = morphemes
+            .iter_mut()
+            .filter_map(|vm| match vm.bbox {
+                Some(bbox) if bbox.2 > 0 && is_ruby_of(r.bbox, bbox) => {
+                    let overlap =
+                        std::cmp::min(rx + rw, bbox.0 + bbox.2) - std::cmp::max(rx, bbox.0);
+                    Some((overlap, vm))
+                }
+                _ => None,
+            })
+            .max_by_key(|(overlap, _)| *overlap);
+        if let Some((_, vm)) = below {
+            let text = match vm.reading_hint.take() {
+                Some(hint) => hint.text + &r.text,
+                None => r.text.clone(),
+            };
+            let agrees = reading_agrees(&vm.morpheme, &text);
+            vm.reading_hint = Some(ReadingHint { text, agrees });
         }
     }
+}
 
-    fn print_entry(entry: &Entry) {
-        for sense in entry.senses() {
-            // let glosses: Vec<&str> = sense.glosses().map(|g| g.text).collect();
-            let glosses: Vec<Gloss> = sense.glosses().collect();
-            println!("{:?}", glosses);
-            println!("\ttopics:");
-            for topic in sense.topics() {
-                println!("\t\t{}", topic);
-            }
-            println!("\tparts of speech:");
-            for pos in sense.parts_of_speech() {
-                println!("\t\t{}", pos);
-            }
-            println!("\tinfos:");
-            for info in sense.infos() {
-                println!("\t\t{}", info);
-            }
-            println!("\tfreetext_infos:");
-            for info in sense.freetext_infos() {
-                println!("\t\t{}", info);
-            }
-            println!("\tcross_references:");
-            for x_ref in sense.cross_references() {
-                println!("\t\t{}", x_ref);
+/// loose check: furigana only cover the kanji of a morpheme, so its reading only has to contain them
+fn reading_agrees(morpheme: &Morpheme, furigana: &str) -> bool {
+    let reading = to_katakana(&morpheme.pronounciation);
+    reading.is_empty() || reading == "*" || reading.contains(&to_katakana(furigana))
+}
+
+/// bounding box of the words of a single line
+fn line_bbox(words: &[&OCRWord]) -> (i32, i32, i32, i32) {
+    let chars_in_line = words
+        .iter()
+        .map(|t| t.text.chars().count() as u32)
+        .sum::<u32>();
+
+    let mut x = std::i32::MAX;
+    let mut y = 0;
+    let mut w = 0;
+    let mut h = 0;
+
+    // averaging out ys and hs, as Tesseract bboxes are not accurate
+    // see https://github.com/tesseract-ocr/tesseract/labels/bounding%20box
+    for word in words {
+        x = std::cmp::min(x, word.x);
+        y += word.y;
+        w = std::cmp::max(w, word.w + word.x - x);
+        h += word.h;
+    }
+    y = (y as f32 / chars_in_line as f32) as i32;
+    h = (h as f32 / chars_in_line as f32) as i32;
+
+    (x, y, w, h)
+}
+
+/// the `index`-th character `c` of `word`, with its single character alternatives if any
+fn to_jpn_char(word: &OCRWord, index: usize, c: char) -> JpnChar {
+    let mut candidates = vec![c];
+    let mut conf = word.conf;
+    if let Some(choices) = word.alternatives.get(index) {
+        let mut choices = choices.clone();
+        choices.sort_by(|a, b| b.conf.total_cmp(&a.conf));
+        for choice in choices {
+            let mut choice_chars = choice.text.chars();
+            match (choice_chars.next(), choice_chars.next()) {
+                (Some(alt), None) if alt == c => conf = choice.conf,
+                (Some(alt), None) if !candidates.contains(&alt) => candidates.push(alt),
+                _ => {}
             }
         }
-        // let readings: Vec<&str> = entry.reading_elements().map(|re| re.text).collect();
+    }
+
+    JpnChar {
+        candidates,
+        selected: 0,
+        conf,
+        bbox: if index == 0 {
+            Some((word.x, word.y, word.w, word.h))
+        } else {
+            None
+        },
     }
 }
@@ -0,0 +1,327 @@
+use std::collections::BTreeMap;
+
+use super::OCRWord;
+use crate::script::{is_hiragana, is_japanese, is_katakana, is_prolonged_sound_mark};
+
+/// how OCR lines get joined into paragraphs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayoutParams {
+    pub join_lines: bool,
+    /// the largest vertical gap between two joined lines, in line heights
+    pub max_line_gap: f32,
+    /// kana words smaller than this fraction of the main text height, right above it, are furigana; 0 disables
+    pub ruby_ratio: f32,
+}
+
+impl Default for LayoutParams {
+    fn default() -> Self {
+        Self {
+            join_lines: true,
+            max_line_gap: 1.,
+            ruby_ratio: 0.6,
+        }
+    }
+}
+
+/// words sharing the same Tesseract line id, in reading order
+#[derive(Debug)]
+pub struct OCRLine<'a> {
+    pub words: Vec<&'a OCRWord>,
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+impl<'a> OCRLine<'a> {
+    fn new(words: Vec<&'a OCRWord>) -> Self {
+        let x = words.iter().map(|w| w.x).min().unwrap_or_default();
+        let y = words.iter().map(|w| w.y).min().unwrap_or_default();
+        let x1 = words.iter().map(|w| w.x + w.w).max().unwrap_or_default();
+        let y1 = words.iter().map(|w| w.y + w.h).max().unwrap_or_default();
+        OCRLine {
+            words,
+            x,
+            y,
+            w: x1 - x,
+            h: y1 - y,
+        }
+    }
+
+    fn first_char(&self) -> Option<char> {
+        self.words.first().and_then(|w| w.text.chars().next())
+    }
+
+    fn last_char(&self) -> Option<char> {
+        self.words.last().and_then(|w| w.text.chars().last())
+    }
+}
+
+/// whether the box `ruby` sits right above the box `main`, overlapping it horizontally
+pub fn is_ruby_of(ruby: (i32, i32, i32, i32), main: (i32, i32, i32, i32)) -> bool {
+    let (rx, ry, rw, rh) = ruby;
+    let (mx, my, mw, mh) = main;
+    let bottom = ry + rh;
+    rx < mx + mw && mx < rx + rw && bottom <= my + mh / 4 && my - bottom <= mh
+}
+
+/// split furigana (ruby) apart from the main text, returning `(main, ruby)`
+pub fn split_ruby<'a>(
+    words: &'a [OCRWord],
+    params: &LayoutParams,
+) -> (Vec<&'a OCRWord>, Vec<&'a OCRWord>) {
+    let mut heights: Vec<i32> = words
+        .iter()
+        .filter(|w| w.text.chars().any(is_japanese))
+        .map(|w| w.h)
+        .collect();
+    if params.ruby_ratio <= 0. || heights.is_empty() {
+        return (words.iter().collect(), vec![]);
+    }
+    heights.sort_unstable();
+    let median = heights[heights.len() / 2] as f32;
+
+    // small kana are not enough, as 'っ', 'ゃ', etc. are small too: furigana also sit above the main text
+    let is_small_kana = |w: &OCRWord| {
+        (w.h as f32) < params.ruby_ratio * median
+            && w.text
+                .chars()
+                .all(|c| is_hiragana(c) || is_katakana(c) || is_prolonged_sound_mark(c))
+    };
+    let is_above = |ruby: &OCRWord, main: &OCRWord| {
+        is_ruby_of(
+            (ruby.x, ruby.y, ruby.w, ruby.h),
+            (main.x, main.y, main.w, main.h),
+        )
+    };
+
+    words.iter().partition(|&w| {
+        !is_small_kana(w)
+            || !words
+                .iter()
+                .any(|main| !is_small_kana(main) && is_above(w, main))
+    })
+}
+
+pub fn lines<'a>(words: &[&'a OCRWord]) -> Vec<OCRLine<'a>> {
+    words
+        .iter()
+        .copied()
+        .fold(
+            BTreeMap::new(),
+            |mut acc: BTreeMap<(u32, u32, u32, u32), Vec<&OCRWord>>, word| {
+                acc.entry(word.line_id).or_default().push(word);
+                acc
+            },
+        )
+        .into_values()
+        .map(OCRLine::new)
+        .collect()
+}
+
+fn is_sentence_end(c: char) -> bool {
+    "。．！？!?」』）…".contains(c)
+}
+
+/// quotes and brackets opening a new utterance, even right after an unfinished sentence
+fn is_opening_bracket(c: char) -> bool {
+    "「『（(【〔［[〈《｛{“‘".contains(c)
+}
+
+/// whether `next` looks like the continuation of the sentence wrapped at the end of `line`
+fn continues(line: &OCRLine, next: &OCRLine, params: &LayoutParams) -> bool {
+    let line_height = std::cmp::max(line.h, next.h) as f32;
+    let gap = (next.y - (line.y + line.h)) as f32;
+    let below = next.y > line.y;
+    let overlapping = next.x < line.x + line.w && line.x < next.x + next.w;
+    let open_sentence = line.last_char().map_or(false, |c| !is_sentence_end(c));
+    let new_utterance = next.first_char().is_some_and(is_opening_bracket);
+
+    below
+        && overlapping
+        && open_sentence
+        && !new_utterance
+        && gap <= params.max_line_gap * line_height
+}
+
+/// group consecutive lines into paragraphs, so sentences wrapped over several lines are analyzed as a whole
+pub fn paragraphs<'a>(lines: Vec<OCRLine<'a>>, params: &LayoutParams) -> Vec<Vec<OCRLine<'a>>> {
+    let mut paragraphs: Vec<Vec<OCRLine>> = vec![];
+    for line in lines {
+        match paragraphs.last_mut() {
+            Some(paragraph)
+                if params.join_lines
+                    && paragraph
+                        .last()
+                        .map_or(false, |last| continues(last, &line, params)) =>
+            {
+                paragraph.push(line)
+            }
+            _ => paragraphs.push(vec![line]),
+        }
+    }
+    paragraphs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str, line: u32, (x, y, w, h): (i32, i32, i32, i32)) -> OCRWord {
+        OCRWord {
+            text: text.to_owned(),
+            line_id: (1, 1, 1, line),
+            word_num: 1,
+            conf: 90.,
+            x,
+            y,
+            w,
+            h,
+            alternatives: vec![],
+        }
+    }
+
+    fn paragraph_texts(words: &[OCRWord], params: &LayoutParams) -> Vec<Vec<String>> {
+        let words: Vec<&OCRWord> = words.iter().collect();
+        paragraphs(lines(&words), params)
+            .iter()
+            .map(|paragraph| {
+                paragraph
+                    .iter()
+                    .flat_map(|line| line.words.iter().map(|w| w.text.clone()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn joins_wrapped_sentence() {
+        let words = [
+            word("今日は天気が", 1, (0, 0, 120, 20)),
+            word("いいですね。", 2, (0, 24, 120, 20)),
+        ];
+        let params = LayoutParams::default();
+        let lines = lines(&words.iter().collect::<Vec<_>>());
+        assert!(continues(&lines[0], &lines[1], &params));
+        assert_eq!(
+            paragraph_texts(&words, &params),
+            [["今日は天気が", "いいですね。"]]
+        );
+
+        let separate = LayoutParams {
+            join_lines: false,
+            ..params
+        };
+        assert_eq!(paragraph_texts(&words, &separate).len(), 2);
+    }
+
+    #[test]
+    fn splits_after_sentence_end() {
+        let words = [
+            word("いいですね。", 1, (0, 0, 120, 20)),
+            word("明日は雨だ", 2, (0, 24, 120, 20)),
+            word("「本当？」", 3, (0, 48, 120, 20)),
+        ];
+        let params = LayoutParams::default();
+        let lines = lines(&words.iter().collect::<Vec<_>>());
+        assert!(!continues(&lines[0], &lines[1], &params));
+        assert!(!continues(&lines[1], &lines[2], &params));
+        assert_eq!(paragraph_texts(&words, &params).len(), 3);
+    }
+
+    #[test]
+    fn splits_before_opening_quote() {
+        let words = [
+            word("彼は言った", 1, (0, 0, 120, 20)),
+            word("『待って』", 2, (0, 24, 120, 20)),
+            word("（笑）", 3, (0, 48, 60, 20)),
+        ];
+        let params = LayoutParams::default();
+        assert_eq!(
+            paragraph_texts(&words, &params),
+            [["彼は言った"], ["『待って』"], ["（笑）"]]
+        );
+    }
+
+    #[test]
+    fn splits_distant_lines() {
+        let params = LayoutParams::default();
+        // side by side, as in two columns or speech bubbles
+        let words = [
+            word("今日は天気が", 1, (0, 0, 120, 20)),
+            word("いいですね", 2, (200, 24, 100, 20)),
+        ];
+        assert_eq!(paragraph_texts(&words, &params).len(), 2);
+        // more than a line height apart
+        let words = [
+            word("今日は天気が", 1, (0, 0, 120, 20)),
+            word("いいですね", 2, (0, 45, 100, 20)),
+        ];
+        assert_eq!(paragraph_texts(&words, &params).len(), 2);
+        let spaced = LayoutParams {
+            max_line_gap: 1.5,
+            ..params
+        };
+        assert_eq!(paragraph_texts(&words, &spaced).len(), 1);
+    }
+
+    fn texts(words: &[&OCRWord]) -> Vec<String> {
+        words.iter().map(|w| w.text.clone()).collect()
+    }
+
+    #[test]
+    fn ruby_boxes() {
+        let main = (0, 30, 40, 20);
+        assert!(is_ruby_of((0, 18, 40, 10), main));
+        // touching the top of the main text, or slightly within it
+        assert!(is_ruby_of((5, 25, 30, 10), main));
+        // beside, below, or too far above the main text
+        assert!(!is_ruby_of((40, 18, 40, 10), main));
+        assert!(!is_ruby_of((0, 52, 40, 10), main));
+        assert!(!is_ruby_of((0, 0, 40, 8), main));
+    }
+
+    #[test]
+    fn splits_ruby_above_kanji() {
+        let words = [
+            word("かんじ", 1, (0, 18, 40, 10)),
+            word("漢字", 2, (0, 30, 40, 20)),
+            word("を読む", 2, (40, 30, 60, 20)),
+        ];
+        let (main, ruby) = split_ruby(&words, &LayoutParams::default());
+        assert_eq!(texts(&main), ["漢字", "を読む"]);
+        assert_eq!(texts(&ruby), ["かんじ"]);
+
+        let disabled = LayoutParams {
+            ruby_ratio: 0.,
+            ..LayoutParams::default()
+        };
+        let (main, ruby) = split_ruby(&words, &disabled);
+        assert_eq!(main.len(), 3);
+        assert!(ruby.is_empty());
+    }
+
+    #[test]
+    fn keeps_small_punctuation_and_kana_in_line() {
+        let words = [
+            word("漢字", 1, (0, 30, 40, 20)),
+            word("、", 1, (40, 40, 8, 8)),
+            word("ちょっと", 1, (48, 30, 80, 20)),
+            word("っ", 1, (128, 38, 10, 10)),
+        ];
+        let (main, ruby) = split_ruby(&words, &LayoutParams::default());
+        assert_eq!(texts(&main), ["漢字", "、", "ちょっと", "っ"]);
+        assert!(ruby.is_empty());
+    }
+
+    #[test]
+    fn keeps_text_made_only_of_small_kana() {
+        let words = [
+            word("ちいさな", 1, (0, 0, 40, 10)),
+            word("もじ", 2, (0, 12, 20, 10)),
+        ];
+        let (main, ruby) = split_ruby(&words, &LayoutParams::default());
+        assert_eq!(texts(&main), ["ちいさな", "もじ"]);
+        assert!(ruby.is_empty());
+    }
+}
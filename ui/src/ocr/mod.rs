@@ -1,10 +1,15 @@
+pub mod cache;
+pub mod choices;
+pub mod eval;
 pub mod jpn;
+pub mod layout;
 
 use log::debug;
-use tesseract::Tesseract;
 
 use anyhow::{anyhow, Result};
 
+use self::choices::{BaseApi, OCRChoice, OCRSymbol};
+
 #[derive(Debug)]
 pub struct OCRWord {
     pub text: String,
@@ -15,25 +20,50 @@ pub struct OCRWord {
     pub y: i32,
     pub w: i32,
     pub h: i32,
+    /// candidates for each character of `text`, best first; empty unless explicitly requested
+    pub alternatives: Vec<Vec<OCRChoice>>,
+}
+
+impl OCRWord {
+    fn contains_center_of(&self, symbol: &OCRSymbol) -> bool {
+        let cx = symbol.x + symbol.w / 2;
+        let cy = symbol.y + symbol.h / 2;
+        self.x <= cx && cx <= self.x + self.w && self.y <= cy && cy <= self.y + self.h
+    }
 }
 
 pub struct OCR {
-    pub lang: String,
+    lang: String,
+    /// initialized by the first recognition
+    api: Option<BaseApi>,
 }
 
 impl OCR {
+    pub fn new(lang: &str) -> OCR {
+        OCR {
+            lang: lang.to_owned(),
+            api: None,
+        }
+    }
+
+    fn api(&mut self) -> Result<&mut BaseApi> {
+        if self.api.is_none() {
+            self.api = Some(BaseApi::new(&self.lang)?);
+        }
+        Ok(self.api.as_mut().unwrap())
+    }
+
     pub fn recognize_words(
-        &self,
+        &mut self,
         frame_data: &[u8],
         width: i32,
         height: i32,
         bytes_per_pixel: i32,
         bytes_per_line: i32,
     ) -> Result<Vec<OCRWord>> {
-        let tsv = Tesseract::new(None, Some(self.lang.as_str()))?
-            .set_frame(frame_data, width, height, bytes_per_pixel, bytes_per_line)?
-            .recognize()?
-            .get_tsv_text(0)?;
+        let api = self.api()?;
+        api.recognize(frame_data, width, height, bytes_per_pixel, bytes_per_line)?;
+        let tsv = api.tsv_text()?;
 
         debug!("OCR raw result: {}", tsv);
 
@@ -44,6 +74,35 @@ impl OCR {
             .collect())
     }
 
+    /// the symbols of the words last recognized, with the candidates Tesseract considered for each
+    pub fn recognized_symbols(&self) -> Vec<OCRSymbol> {
+        self.api.as_ref().map(BaseApi::symbols).unwrap_or_default()
+    }
+
+    /// fill the `alternatives` of the words matching `filter`, from the symbols lying within their bounding box
+    pub fn attach_alternatives(
+        words: &mut [OCRWord],
+        symbols: &[OCRSymbol],
+        filter: impl Fn(&OCRWord) -> bool,
+    ) {
+        for word in words.iter_mut().filter(|w| filter(w)) {
+            let alternatives: Vec<Vec<OCRChoice>> = symbols
+                .iter()
+                .filter(|s| word.contains_center_of(s))
+                .map(|s| s.choices.clone())
+                .collect();
+            // symbols and characters can only be paired when Tesseract segmented the word consistently
+            if alternatives.len() == word.text.chars().count() {
+                word.alternatives = alternatives;
+            } else {
+                debug!(
+                    "could not match symbols to the characters of {:?}",
+                    word.text
+                );
+            }
+        }
+    }
+
     fn maybe_word(&self, s: &str) -> Result<OCRWord> {
         let tokens: Vec<String> = s.split_terminator('\t').map(String::from).collect();
         if tokens.len() < 12 {
@@ -78,6 +137,7 @@ impl OCR {
             y,
             w,
             h,
+            alternatives: vec![],
         })
     }
 }
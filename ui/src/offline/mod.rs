@@ -0,0 +1,274 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::channel;
+
+use anyhow::{anyhow, Result};
+use image::{Rgba, RgbaImage};
+use imageproc::drawing::draw_hollow_rect_mut;
+use imageproc::rect::Rect;
+use log::{debug, info, warn};
+use notify::event::{AccessKind, AccessMode, ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use sdl2::ttf::{FontStyle, Sdl2TtfContext};
+use serde::Serialize;
+
+use crate::config::KSConfig;
+use crate::dict::glosses::Dictionaries;
+use crate::dict::{LookupKey, LookupParams};
+use crate::ocr::jpn::{JpnOCR, JpnText, VisualMorpheme};
+use crate::overlay::sdl::{argb_to_sdl_color, print_to_new_pixels, TextMeta};
+use crate::pipeline::recognize_capture;
+use crate::script::is_kanji;
+
+const ANNOTATED_SUFFIX: &str = ".annotated.png";
+
+#[derive(Serialize, Debug)]
+pub struct ImageResult {
+    pub image: PathBuf,
+    pub texts: Vec<TextResult>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TextResult {
+    pub text: String,
+    /// `[x, y, w, h]` of each line of the text
+    pub lines: Vec<[i32; 4]>,
+    pub morphemes: Vec<MorphemeResult>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MorphemeResult {
+    pub text: String,
+    pub reading: String,
+    pub lemma: String,
+    pub part_of_speech: String,
+    pub inflection_type: Option<String>,
+    pub inflection_form: Option<String>,
+    pub bbox: Option<[i32; 4]>,
+    pub furigana: Option<String>,
+    /// the dictionary the morpheme was found in, the form found, and which form it is
+    pub dictionary: Option<String>,
+    pub headword: Option<String>,
+    pub found_via: Option<LookupKey>,
+    /// the glosses, per sense
+    pub glosses: Vec<Vec<String>>,
+}
+
+impl MorphemeResult {
+    fn new(vm: &VisualMorpheme, dictionaries: &Dictionaries, lookup_params: &LookupParams) -> Self {
+        let morpheme = &vm.morpheme;
+        let glosses = dictionaries.glosses(morpheme, lookup_params);
+        MorphemeResult {
+            text: morpheme.text.clone(),
+            reading: morpheme.pronounciation.clone(),
+            lemma: morpheme.lemma.clone(),
+            part_of_speech: morpheme.part_of_speech.clone(),
+            inflection_type: morpheme.inflection_type.clone(),
+            inflection_form: morpheme.inflection_form.clone(),
+            bbox: vm.bbox.map(|(x, y, w, h)| [x, y, w, h]),
+            furigana: vm.reading_hint.as_ref().map(|hint| hint.text.clone()),
+            dictionary: glosses.as_ref().map(|g| g.dictionary.clone()),
+            headword: glosses.as_ref().map(|g| g.headword.clone()),
+            found_via: glosses.as_ref().map(|g| g.key),
+            glosses: glosses.map(|g| g.senses).unwrap_or_default(),
+        }
+    }
+}
+
+impl TextResult {
+    fn new(jpn_text: &JpnText, dictionaries: &Dictionaries, lookup_params: &LookupParams) -> Self {
+        TextResult {
+            text: jpn_text.text(),
+            lines: jpn_text
+                .lines
+                .iter()
+                .map(|&(x, y, w, h)| [x, y, w, h])
+                .collect(),
+            morphemes: jpn_text
+                .morphemes
+                .iter()
+                .map(|vm| MorphemeResult::new(vm, dictionaries, lookup_params))
+                .collect(),
+        }
+    }
+}
+
+fn argb_to_rgba(argb: u32) -> Rgba<u8> {
+    Rgba([(argb >> 16) as u8, (argb >> 8) as u8, argb as u8, 255])
+}
+
+/// alpha-blend SDL rendered pixels (BGRA in memory) onto `img`
+fn blend_pixels(img: &mut RgbaImage, data: &[u8], x: i32, y: i32, width: u32) {
+    for (i, px) in data.chunks_exact(4).enumerate() {
+        let (dx, dy) = (x + (i as u32 % width) as i32, y + (i as u32 / width) as i32);
+        if dx < 0 || dy < 0 || dx >= img.width() as i32 || dy >= img.height() as i32 {
+            continue;
+        }
+        let alpha = px[3] as u32;
+        let target = img.get_pixel_mut(dx as u32, dy as u32);
+        for (channel, source) in [px[2], px[1], px[0]].into_iter().enumerate() {
+            target[channel] =
+                ((source as u32 * alpha + target[channel] as u32 * (255 - alpha)) / 255) as u8;
+        }
+    }
+}
+
+/// recognition of image files rather than screen captures, e.g. screenshots taken by an emulator
+pub struct OfflineOCR {
+    ocr: JpnOCR,
+    dictionaries: Dictionaries,
+    config: KSConfig,
+    sdl2_ttf_ctx: Sdl2TtfContext,
+    font_path: PathBuf,
+}
+
+impl OfflineOCR {
+    pub fn new(ocr: JpnOCR, config: KSConfig, font_path: PathBuf) -> Result<OfflineOCR> {
+        Ok(OfflineOCR {
+            ocr,
+            dictionaries: Dictionaries::load(&config),
+            config,
+            sdl2_ttf_ctx: sdl2::ttf::init()?,
+            font_path,
+        })
+    }
+
+    /// a copy of the image with the recognized lines boxed and readings above the words with kanji
+    pub fn annotate(&self, img: &RgbaImage, jpn_texts: &[JpnText]) -> RgbaImage {
+        let mut annotated = img.clone();
+        let box_color = argb_to_rgba(self.config.colors.highlight);
+
+        for jpn_text in jpn_texts {
+            for &(x, y, w, h) in &jpn_text.lines {
+                if w > 0 && h > 0 {
+                    draw_hollow_rect_mut(
+                        &mut annotated,
+                        Rect::at(x, y).of_size(w as u32, h as u32),
+                        box_color,
+                    );
+                }
+            }
+
+            for vm in &jpn_text.morphemes {
+                let (x, y, _, h) = match vm.bbox.filter(|bbox| bbox.2 > 0) {
+                    Some(bbox) => bbox,
+                    None => continue,
+                };
+                if !vm.morpheme.text.chars().any(is_kanji) {
+                    continue;
+                }
+                let text_meta = TextMeta {
+                    font_path: &self.font_path,
+                    color: argb_to_sdl_color(self.config.colors.hint),
+                    point_size: (h / 2).max(8) as u16,
+                    styles: FontStyle::empty(),
+                };
+                let (data, width, height) = print_to_new_pixels(
+                    &self.sdl2_ttf_ctx,
+                    &vm.morpheme.pronounciation,
+                    &text_meta,
+                    self.config.colors.hint_bg,
+                    0,
+                );
+                blend_pixels(&mut annotated, &data, x, y - height as i32, width);
+            }
+        }
+
+        annotated
+    }
+
+    /// recognize and analyze an image file, writing the results as JSON and an annotated copy of the image
+    /// into `output` (or next to the image); returns the path of the JSON file
+    pub fn process_image(&mut self, path: &Path, output: Option<&Path>) -> Result<PathBuf> {
+        let img = image::open(path)?.to_rgba8();
+        let jpn_texts = recognize_capture(&mut self.ocr, img.clone(), &self.config.preproc)?;
+
+        let dir = match output {
+            Some(dir) => dir.to_path_buf(),
+            None => path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        };
+        std::fs::create_dir_all(&dir)?;
+        let stem = path
+            .file_stem()
+            .ok_or_else(|| anyhow!("not a file: {:?}", path))?
+            .to_string_lossy();
+
+        let lookup_params = LookupParams::from(&self.config.glosses);
+        let result = ImageResult {
+            image: path.to_path_buf(),
+            texts: jpn_texts
+                .iter()
+                .map(|jpn_text| TextResult::new(jpn_text, &self.dictionaries, &lookup_params))
+                .collect(),
+        };
+        let json_path = dir.join(format!("{}.json", stem));
+        std::fs::write(&json_path, serde_json::to_string_pretty(&result)?)?;
+
+        self.annotate(&img, &jpn_texts)
+            .save(dir.join(format!("{}{}", stem, ANNOTATED_SUFFIX)))?;
+
+        Ok(json_path)
+    }
+
+    /// process the given images, going on with the others when one fails; fails once they are all done if
+    /// any did
+    pub fn files(&mut self, images: &[PathBuf], output: Option<&Path>) -> Result<()> {
+        let mut failures = 0;
+        for image in images {
+            match self.process_image(image, output) {
+                Ok(json_path) => info!("{:?} analyzed into {:?}", image, json_path),
+                Err(e) => {
+                    warn!("Failed to process {:?}: {:?}", image, e);
+                    failures += 1;
+                }
+            }
+        }
+        if failures > 0 {
+            return Err(anyhow!(
+                "{} of {} images could not be processed",
+                failures,
+                images.len()
+            ));
+        }
+        Ok(())
+    }
+
+    /// process the images written into `dir` until the program is stopped
+    pub fn watch(&mut self, dir: &Path, output: Option<&Path>) -> Result<()> {
+        let (tx, rx) = channel();
+        let mut watcher = RecommendedWatcher::new(
+            move |result: notify::Result<Event>| match result {
+                // images are only complete once written and closed, or moved into place
+                Ok(event)
+                    if matches!(
+                        event.kind,
+                        EventKind::Access(AccessKind::Close(AccessMode::Write))
+                            | EventKind::Modify(ModifyKind::Name(RenameMode::To))
+                    ) =>
+                {
+                    for path in event.paths {
+                        let _ = tx.send(path);
+                    }
+                }
+                _ => {}
+            },
+            notify::Config::default(),
+        )?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        info!("Watching {:?} for new images", dir);
+
+        for path in rx {
+            let is_image = image::ImageFormat::from_path(&path).is_ok();
+            let is_annotation = path.to_string_lossy().ends_with(ANNOTATED_SUFFIX);
+            if !is_image || is_annotation {
+                continue;
+            }
+            debug!("processing {:?}", path);
+            match self.process_image(&path, output) {
+                Ok(json_path) => info!("{:?} analyzed into {:?}", path, json_path),
+                Err(e) => warn!("Failed to process {:?}: {:?}", path, e),
+            }
+        }
+
+        Ok(())
+    }
+}
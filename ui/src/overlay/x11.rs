@@ -1,3 +1,5 @@
+use std::time::{Duration, Instant};
+
 use anyhow::Result;
 use x11rb::connection::Connection;
 use x11rb::protocol::shape;
@@ -6,10 +8,13 @@ use x11rb::protocol::xfixes::{
 };
 use x11rb::protocol::xproto::{
     AtomEnum, ClientMessageEvent, ColormapAlloc, ColormapWrapper, ConfigureWindowAux,
-    ConnectionExt as _, CreateGCAux, CreateWindowAux, EventMask, GcontextWrapper, ImageFormat,
-    PropMode, Rectangle, Screen, StackMode, Window, WindowClass,
+    ConnectionExt as _, CreateGCAux, CreateWindowAux, CursorWrapper, EventMask, FontWrapper,
+    GcontextWrapper, GrabMode, GrabStatus, ImageFormat, PropMode, Rectangle, Screen, StackMode,
+    Window, WindowClass,
 };
+use x11rb::protocol::Event;
 use x11rb::wrapper::ConnectionExt as _;
+use x11rb::CURRENT_TIME;
 
 pub fn xfixes_init<Conn>(conn: &Conn)
 where
@@ -106,12 +111,10 @@ where
     Ok(())
 }
 
-pub fn find_window<Conn>(conn: &Conn, root_win_id: u32, name: &str) -> Result<Window>
+fn has_name<Conn>(conn: &Conn, win_id: Window, name: &str) -> Result<bool>
 where
     Conn: Connection,
 {
-    let tree = conn.query_tree(root_win_id)?.reply()?.children;
-
     let net_wm_name = conn
         .intern_atom(false, "_NET_WM_NAME".as_bytes())?
         .reply()?
@@ -122,24 +125,222 @@ where
         .reply()?
         .atom;
 
+    let reply = conn
+        .get_property(false, win_id, AtomEnum::WM_NAME, AtomEnum::STRING, 0, 100)?
+        .reply()?;
+    let w_name = std::str::from_utf8(reply.value.as_slice());
+    if w_name == Ok(name) {
+        return Ok(true);
+    }
+    let reply = conn
+        .get_property(false, win_id, net_wm_name, utf8_string, 0, 100)?
+        .reply()?;
+    let w_name = std::str::from_utf8(reply.value.as_slice());
+    Ok(w_name == Ok(name))
+}
+
+pub fn find_window<Conn>(conn: &Conn, root_win_id: u32, name: &str) -> Result<Window>
+where
+    Conn: Connection,
+{
+    let tree = conn.query_tree(root_win_id)?.reply()?.children;
+
     for w in tree {
-        let reply = conn
-            .get_property(false, w, AtomEnum::WM_NAME, AtomEnum::STRING, 0, 100)?
-            .reply()?;
-        let w_name = std::str::from_utf8(reply.value.as_slice());
-        if w_name == Ok(name) {
+        if has_name(conn, w, name)? {
+            return Ok(w);
+        }
+    }
+
+    Err(anyhow::anyhow!("no window for name {}", name))
+}
+
+/// the windows below `win_id`, parents before their children
+fn descendants<Conn>(conn: &Conn, win_id: Window) -> Result<Vec<Window>>
+where
+    Conn: Connection,
+{
+    let mut windows = vec![];
+    let mut stack = vec![win_id];
+    while let Some(w) = stack.pop() {
+        let children = conn.query_tree(w)?.reply()?.children;
+        windows.extend(&children);
+        stack.extend(children.iter().rev());
+    }
+    Ok(windows)
+}
+
+/// the instance and class names of a window's WM_CLASS
+pub fn window_class<Conn>(conn: &Conn, win_id: Window) -> Result<Vec<String>>
+where
+    Conn: Connection,
+{
+    let reply = conn
+        .get_property(false, win_id, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 100)?
+        .reply()?;
+    Ok(reply
+        .value
+        .split(|&b| b == 0)
+        .filter(|part| !part.is_empty())
+        .map(|part| String::from_utf8_lossy(part).into_owned())
+        .collect())
+}
+
+/// whether a window is an application's top-level window, as opposed to a window manager's frame
+fn is_client<Conn>(conn: &Conn, win_id: Window) -> Result<bool>
+where
+    Conn: Connection,
+{
+    let wm_state = conn
+        .intern_atom(false, "WM_STATE".as_bytes())?
+        .reply()?
+        .atom;
+    let reply = conn
+        .get_property(false, win_id, wm_state, AtomEnum::ANY, 0, 0)?
+        .reply()?;
+    Ok(reply.type_ != x11rb::NONE)
+}
+
+/// finds an application's window by WM_CLASS (instance or class name) or else by name, however deep
+/// the window manager nests it
+pub fn find_client_window<Conn>(conn: &Conn, root_win_id: u32, query: &str) -> Result<Window>
+where
+    Conn: Connection,
+{
+    let windows = descendants(conn, root_win_id)?;
+    for &w in &windows {
+        if window_class(conn, w)?.iter().any(|c| c == query) {
             return Ok(w);
         }
-        let reply = conn
-            .get_property(false, w, net_wm_name, utf8_string, 0, 100)?
-            .reply()?;
-        let w_name = std::str::from_utf8(reply.value.as_slice());
-        if w_name == Ok(name) {
+    }
+    for &w in &windows {
+        if has_name(conn, w, query)? {
             return Ok(w);
         }
     }
+    Err(anyhow::anyhow!("no window for class or name {}", query))
+}
 
-    Err(anyhow::anyhow!("no window for name {}", name))
+/// the keycodes producing `keysym`
+fn keycodes<Conn>(conn: &Conn, keysym: u32) -> Result<Vec<u8>>
+where
+    Conn: Connection,
+{
+    let (min, max) = (conn.setup().min_keycode, conn.setup().max_keycode);
+    let mapping = conn.get_keyboard_mapping(min, max - min + 1)?.reply()?;
+    let per_keycode = std::cmp::max(mapping.keysyms_per_keycode as usize, 1);
+    Ok(mapping
+        .keysyms
+        .chunks(per_keycode)
+        .enumerate()
+        .filter(|(_, keysyms)| keysyms.contains(&keysym))
+        .map(|(i, _)| min + i as u8)
+        .collect())
+}
+
+/// the application's window at the next click, under a crosshair cursor; given up on `Escape` or after
+/// `timeout`, the other events received meanwhile being left in `skipped`
+pub fn pick_window<Conn>(
+    conn: &Conn,
+    root_win_id: u32,
+    timeout: Duration,
+    skipped: &mut Vec<Event>,
+) -> Result<Window>
+where
+    Conn: Connection,
+{
+    const XK_ESCAPE: u32 = 0xff1b;
+    let escape = keycodes(conn, XK_ESCAPE)?;
+
+    // glyphs from the standard cursor font
+    const XC_CROSSHAIR: u16 = 34;
+    let font = FontWrapper::open_font(conn, "cursor".as_bytes())?;
+    let cursor = CursorWrapper::create_glyph_cursor(
+        conn,
+        font.font(),
+        font.font(),
+        XC_CROSSHAIR,
+        XC_CROSSHAIR + 1,
+        0,
+        0,
+        0,
+        0xffff,
+        0xffff,
+        0xffff,
+    )?;
+
+    let grab = conn
+        .grab_pointer(
+            false,
+            root_win_id,
+            // the request's mask only has room for pointer events
+            u32::from(EventMask::BUTTON_PRESS) as u16,
+            GrabMode::ASYNC,
+            GrabMode::ASYNC,
+            x11rb::NONE,
+            cursor.cursor(),
+            CURRENT_TIME,
+        )?
+        .reply()?;
+    if grab.status != GrabStatus::SUCCESS {
+        return Err(anyhow::anyhow!(
+            "failed to grab the pointer: {:?}",
+            grab.status
+        ));
+    }
+
+    // without the keyboard, picking can still be given up by waiting
+    conn.grab_keyboard(
+        false,
+        root_win_id,
+        CURRENT_TIME,
+        GrabMode::ASYNC,
+        GrabMode::ASYNC,
+    )?
+    .reply()?;
+
+    let deadline = Instant::now() + timeout;
+    let picked = loop {
+        match conn.poll_for_event()? {
+            Some(Event::ButtonPress(event)) => break Some(event.child),
+            Some(Event::KeyPress(event)) if escape.contains(&event.detail) => break None,
+            Some(Event::KeyPress(_) | Event::KeyRelease(_)) => {}
+            Some(event) => skipped.push(event),
+            None if Instant::now() >= deadline => break None,
+            None => std::thread::sleep(Duration::from_millis(20)),
+        }
+    };
+    conn.ungrab_pointer(CURRENT_TIME)?;
+    conn.ungrab_keyboard(CURRENT_TIME)?;
+    conn.flush()?;
+
+    let picked = picked
+        .filter(|&picked| picked != x11rb::NONE)
+        .ok_or_else(|| anyhow::anyhow!("no window picked"))?;
+    // the click lands on the window manager's frame, if any, around the application's window
+    if is_client(conn, picked)? {
+        return Ok(picked);
+    }
+    for w in descendants(conn, picked)? {
+        if is_client(conn, w)? {
+            return Ok(w);
+        }
+    }
+    Ok(picked)
+}
+
+/// the window's ancestor directly below the root, e.g. the window manager's frame around it
+pub fn top_level<Conn>(conn: &Conn, root_win_id: u32, win_id: Window) -> Result<Window>
+where
+    Conn: Connection,
+{
+    let mut w = win_id;
+    loop {
+        let parent = conn.query_tree(w)?.reply()?.parent;
+        if parent == root_win_id || parent == x11rb::NONE {
+            return Ok(w);
+        }
+        w = parent;
+    }
 }
 
 pub fn raise<Conn>(conn: &Conn, win_id: u32) -> Result<()>
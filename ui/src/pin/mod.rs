@@ -0,0 +1,157 @@
+use image::RgbaImage;
+use std::time::{Duration, Instant};
+
+/// how a pinned region is watched for changes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PinParams {
+    /// the fraction of the region's pixels that must change for its text to be recognized again
+    pub change_threshold: f32,
+    /// how long the region must stay still before recognition, e.g. while a line of dialogue is typed out
+    pub debounce: Duration,
+}
+
+impl Default for PinParams {
+    fn default() -> Self {
+        Self {
+            change_threshold: 0.01,
+            debounce: Duration::from_millis(300),
+        }
+    }
+}
+
+/// the fraction of pixels noticeably different between two images; images of different sizes differ entirely
+pub fn changed_fraction(a: &RgbaImage, b: &RgbaImage) -> f32 {
+    const PIXEL_TOLERANCE: i32 = 16;
+    if a.dimensions() != b.dimensions() {
+        return 1.;
+    }
+    let changed = a
+        .pixels()
+        .zip(b.pixels())
+        .filter(|(p, q)| (0..3).any(|c| (p[c] as i32 - q[c] as i32).abs() > PIXEL_TOLERANCE))
+        .count();
+    changed as f32 / std::cmp::max(a.width() * a.height(), 1) as f32
+}
+
+/// an area of the screen recognized again whenever its pixels change, once they settle
+pub struct PinnedRegion {
+    pub x: i32,
+    pub y: i32,
+    pub w: u32,
+    pub h: u32,
+    params: PinParams,
+    /// the area's pixels when its recognition results were last drawn
+    reference: Option<RgbaImage>,
+    /// the area's pixels when last polled, if they differ from the reference, and since when they have been so
+    changed: Option<(RgbaImage, Instant)>,
+}
+
+impl PinnedRegion {
+    pub fn new(x: i32, y: i32, w: u32, h: u32, params: PinParams) -> Self {
+        Self {
+            x,
+            y,
+            w,
+            h,
+            params,
+            reference: None,
+            changed: None,
+        }
+    }
+
+    /// the pixels the next ones are compared to; none makes the next polled ones the reference
+    pub fn set_reference(&mut self, img: Option<RgbaImage>) {
+        self.reference = img;
+        self.changed = None;
+    }
+
+    /// compare the area's current pixels to the reference; whether they changed and settled since, i.e.
+    /// whether the area is due for recognition
+    pub fn poll(&mut self, img: RgbaImage) -> bool {
+        let reference = match &self.reference {
+            Some(reference) => reference,
+            None => {
+                self.set_reference(Some(img));
+                return false;
+            }
+        };
+        if changed_fraction(reference, &img) < self.params.change_threshold {
+            self.changed = None;
+            return false;
+        }
+        match &self.changed {
+            Some((changed, since))
+                if changed_fraction(changed, &img) < self.params.change_threshold =>
+            {
+                since.elapsed() >= self.params.debounce
+            }
+            _ => {
+                self.changed = Some((img, Instant::now()));
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+    const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+    /// a white 100x100 image, its first `changed` pixels black
+    fn with_changed(changed: u32) -> RgbaImage {
+        RgbaImage::from_fn(
+            100,
+            100,
+            |x, y| {
+                if y * 100 + x < changed {
+                    BLACK
+                } else {
+                    WHITE
+                }
+            },
+        )
+    }
+
+    #[test]
+    fn identical_and_fully_changed() {
+        let white = with_changed(0);
+        assert_eq!(changed_fraction(&white, &white.clone()), 0.);
+        assert_eq!(changed_fraction(&white, &with_changed(100 * 100)), 1.);
+        // slight color shifts, e.g. from compression or dithering, are no change
+        let shifted = RgbaImage::from_pixel(100, 100, Rgba([240, 250, 245, 255]));
+        assert_eq!(changed_fraction(&white, &shifted), 0.);
+    }
+
+    #[test]
+    fn different_sizes() {
+        let a = RgbaImage::from_pixel(100, 100, WHITE);
+        let b = RgbaImage::from_pixel(100, 99, WHITE);
+        assert_eq!(changed_fraction(&a, &b), 1.);
+        let empty = RgbaImage::new(0, 0);
+        assert_eq!(changed_fraction(&empty, &empty), 0.);
+    }
+
+    #[test]
+    fn changes_around_threshold() {
+        let params = PinParams {
+            change_threshold: 0.01,
+            debounce: Duration::ZERO,
+        };
+        let reference = with_changed(0);
+        let below = with_changed(99);
+        let above = with_changed(101);
+        assert!(changed_fraction(&reference, &below) < params.change_threshold);
+        assert!(changed_fraction(&reference, &above) > params.change_threshold);
+
+        let mut region = PinnedRegion::new(0, 0, 100, 100, params);
+        assert!(!region.poll(reference));
+        assert!(!region.poll(below));
+        // changed, then still the next time
+        assert!(!region.poll(above.clone()));
+        assert!(region.poll(above));
+    }
+}
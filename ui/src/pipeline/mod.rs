@@ -0,0 +1,37 @@
+use anyhow::Result;
+use image::RgbaImage;
+
+use crate::config::Preproc;
+use crate::ocr::jpn::{JpnOCR, JpnText};
+use crate::preproc::{preprocess, Deskew};
+
+/// recognize and analyze the text of a preprocessed capture, with boxes mapped back onto the original capture
+pub fn recognize_preprocessed(
+    ocr: &mut JpnOCR,
+    img: &RgbaImage,
+    deskew: Option<Deskew>,
+) -> Result<Vec<JpnText>> {
+    let mut jpn_texts = ocr.recognize(
+        img.as_raw(),
+        img.width() as i32,
+        img.height() as i32,
+        4,
+        4 * img.width() as i32,
+    )?;
+    if let Some(deskew) = deskew {
+        for jpn_text in jpn_texts.iter_mut() {
+            jpn_text.map_boxes(|bbox| deskew.to_original(bbox));
+        }
+    }
+    Ok(jpn_texts)
+}
+
+/// preprocess a capture, then recognize and analyze its text
+pub fn recognize_capture(
+    ocr: &mut JpnOCR,
+    capture: RgbaImage,
+    preproc: &Preproc,
+) -> Result<Vec<JpnText>> {
+    let (img, deskew) = preprocess(capture, preproc);
+    recognize_preprocessed(ocr, &img, deskew)
+}
@@ -0,0 +1,219 @@
+use image::{imageops, Rgba, RgbaImage};
+use imageproc::geometric_transformations::{rotate_about_center, Interpolation};
+
+use crate::config::Preproc;
+
+/// the rotation undone before OCR, to map recognized boxes back onto the original capture
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Deskew {
+    /// radians, clockwise
+    pub angle: f32,
+    /// size of the deskewed image, enlarged so that the corners of the capture stay within it
+    pub width: u32,
+    pub height: u32,
+    /// where the capture lies within the deskewed image, before rotation
+    pub offset_x: u32,
+    pub offset_y: u32,
+}
+
+impl Deskew {
+    /// the deskewing of a `width` x `height` capture by `angle`, on a canvas fitting the rotated capture
+    pub fn new(angle: f32, width: u32, height: u32) -> Deskew {
+        let (s, c) = angle.sin_cos();
+        let (s, c) = (s.abs(), c.abs());
+        let (w, h) = (width as f32, height as f32);
+        // the same margin on both sides, so that the capture and the canvas share their center
+        let margin =
+            |rotated: f32, size: u32| (rotated.ceil() as u32).saturating_sub(size).div_ceil(2);
+        let (offset_x, offset_y) = (margin(w * c + h * s, width), margin(w * s + h * c, height));
+        Deskew {
+            angle,
+            width: width + 2 * offset_x,
+            height: height + 2 * offset_y,
+            offset_x,
+            offset_y,
+        }
+    }
+
+    /// bounding box, in the original capture, of a box found in the deskewed image
+    pub fn to_original(&self, bbox: (i32, i32, i32, i32)) -> (i32, i32, i32, i32) {
+        let (ox, oy) = (self.offset_x as f32, self.offset_y as f32);
+        if self.angle == 0. {
+            let (x, y, w, h) = bbox;
+            return (x - ox as i32, y - oy as i32, w, h);
+        }
+        let (x, y, w, h) = bbox;
+        let (cx, cy) = (self.width as f32 / 2., self.height as f32 / 2.);
+        let (s, c) = self.angle.sin_cos();
+        let corners = [(x, y), (x + w, y), (x, y + h), (x + w, y + h)].map(|(px, py)| {
+            let (dx, dy) = (px as f32 - cx, py as f32 - cy);
+            (cx + dx * c - dy * s - ox, cy + dx * s + dy * c - oy)
+        });
+        let x0 = corners.iter().map(|p| p.0).fold(f32::MAX, f32::min);
+        let y0 = corners.iter().map(|p| p.1).fold(f32::MAX, f32::min);
+        let x1 = corners.iter().map(|p| p.0).fold(f32::MIN, f32::max);
+        let y1 = corners.iter().map(|p| p.1).fold(f32::MIN, f32::max);
+        (
+            x0.round() as i32,
+            y0.round() as i32,
+            (x1 - x0).round() as i32,
+            (y1 - y0).round() as i32,
+        )
+    }
+}
+
+/// prepare a screen capture for OCR
+pub fn preprocess(mut img: RgbaImage, preproc: &Preproc) -> (RgbaImage, Option<Deskew>) {
+    imageops::colorops::contrast_in_place(&mut img, preproc.contrast);
+
+    if !preproc.deskew {
+        return (img, None);
+    }
+
+    let angle = detect_skew(&img, preproc.max_skew_angle.to_radians());
+    if angle == 0. {
+        return (img, None);
+    }
+
+    let deskew = Deskew::new(angle, img.width(), img.height());
+    let background = background_color(&img);
+    // rotating within the capture's own bounds would cut off its corners, and the text there
+    let mut canvas = RgbaImage::from_pixel(deskew.width, deskew.height, background);
+    imageops::replace(
+        &mut canvas,
+        &img,
+        deskew.offset_x as i64,
+        deskew.offset_y as i64,
+    );
+    let img = rotate_about_center(&canvas, -angle, Interpolation::Bilinear, background);
+
+    (img, Some(deskew))
+}
+
+fn luminance(p: &Rgba<u8>) -> u8 {
+    ((p[0] as u32 * 299 + p[1] as u32 * 587 + p[2] as u32 * 114) / 1000) as u8
+}
+
+/// the average color of the capture's border, assumed to be the text background
+fn background_color(img: &RgbaImage) -> Rgba<u8> {
+    let (w, h) = img.dimensions();
+    let border = img
+        .enumerate_pixels()
+        .filter(|(x, y, _)| *x == 0 || *y == 0 || *x == w - 1 || *y == h - 1)
+        .map(|(_, _, p)| p);
+    let mut sums = [0u64; 4];
+    let mut count = 0;
+    for p in border {
+        for (sum, channel) in sums.iter_mut().zip(p.0) {
+            *sum += channel as u64;
+        }
+        count += 1;
+    }
+    Rgba(sums.map(|sum| (sum / std::cmp::max(count, 1)) as u8))
+}
+
+/// the angle, in radians within `[-max_angle, max_angle]`, by which the text lines of the image are rotated clockwise
+///
+/// projection-profile analysis: the rotation for which the ink projected across the text lines gives the sharpest
+/// profile (the highest sum of squared bins) is the one aligned with the lines
+pub fn detect_skew(img: &RgbaImage, max_angle: f32) -> f32 {
+    const ANALYSIS_WIDTH: u32 = 400;
+    const STEP: f32 = 0.25 * std::f32::consts::PI / 180.;
+    // do not bother rotating for a marginal improvement over the original image
+    const MIN_GAIN: f64 = 1.05;
+
+    if max_angle <= 0. || img.width() < 2 || img.height() < 2 {
+        return 0.;
+    }
+
+    let img = if img.width() > ANALYSIS_WIDTH {
+        let height = (img.height() * ANALYSIS_WIDTH / img.width()).max(1);
+        imageops::resize(img, ANALYSIS_WIDTH, height, imageops::FilterType::Triangle)
+    } else {
+        img.clone()
+    };
+
+    // ink: whatever stands out from the median luminance, i.e. from the background
+    let mut lums: Vec<u8> = img.pixels().map(luminance).collect();
+    lums.sort_unstable();
+    let median = lums[lums.len() / 2] as i32;
+    let ink: Vec<(f32, f32)> = img
+        .enumerate_pixels()
+        .filter(|(_, _, p)| (luminance(p) as i32 - median).abs() > 48)
+        .map(|(x, y, _)| (x as f32, y as f32))
+        .collect();
+    if ink.is_empty() {
+        return 0.;
+    }
+
+    let diagonal = ((img.width().pow(2) + img.height().pow(2)) as f32).sqrt();
+    let sharpness = |angle: f32| {
+        let (s, c) = angle.sin_cos();
+        let mut bins = vec![0u32; 2 * diagonal as usize + 1];
+        for (x, y) in &ink {
+            let projected = y * c - x * s + diagonal;
+            bins[projected as usize] += 1;
+        }
+        bins.iter().map(|&b| (b as f64).powi(2)).sum::<f64>()
+    };
+
+    let steps = (max_angle / STEP) as i32;
+    let (best_angle, best) = (-steps..=steps)
+        .map(|i| i as f32 * STEP)
+        .map(|angle| (angle, sharpness(angle)))
+        .fold((0., f64::MIN), |acc, candidate| {
+            if candidate.1 > acc.1 {
+                candidate
+            } else {
+                acc
+            }
+        });
+
+    if best > sharpness(0.) * MIN_GAIN {
+        best_angle
+    } else {
+        0.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canvas_fits_rotated_capture() {
+        let deskew = Deskew::new(0., 100, 50);
+        assert_eq!((deskew.width, deskew.height), (100, 50));
+        assert_eq!((deskew.offset_x, deskew.offset_y), (0, 0));
+
+        let deskew = Deskew::new(5f32.to_radians(), 100, 50);
+        assert_eq!((deskew.width, deskew.height), (104, 60));
+        assert_eq!((deskew.offset_x, deskew.offset_y), (2, 5));
+    }
+
+    #[test]
+    fn maps_boxes_back_onto_capture() {
+        let angle = 5f32.to_radians();
+        let deskew = Deskew::new(angle, 100, 50);
+        // where a point of the capture ends up once placed on the canvas and rotated back by `angle`
+        let deskewed = |(x, y): (f32, f32)| {
+            let (cx, cy) = (deskew.width as f32 / 2., deskew.height as f32 / 2.);
+            let (dx, dy) = (
+                x + deskew.offset_x as f32 - cx,
+                y + deskew.offset_y as f32 - cy,
+            );
+            let (s, c) = angle.sin_cos();
+            (cx + dx * c + dy * s, cy - dx * s + dy * c)
+        };
+        // the capture's corners, cut off by a rotation within the capture's own bounds
+        for corner in [(0., 0.), (100., 0.), (0., 50.), (100., 50.)] {
+            let (x, y) = deskewed(corner);
+            assert!(x >= 0. && x <= deskew.width as f32, "{:?}", corner);
+            assert!(y >= 0. && y <= deskew.height as f32, "{:?}", corner);
+            let (ox, oy, w, h) = deskew.to_original((x.round() as i32, y.round() as i32, 0, 0));
+            assert!((ox - corner.0 as i32).abs() <= 1, "{:?}", corner);
+            assert!((oy - corner.1 as i32).abs() <= 1, "{:?}", corner);
+            assert_eq!((w, h), (0, 0));
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use image::{GrayImage, Luma, RgbaImage};
+use imageproc::region_labelling::{connected_components, Connectivity};
+
+/// how a text block is grown around the cursor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RegionParams {
+    /// the largest gap between two glyphs of a block, in glyph sizes
+    pub max_gap: f32,
+    /// shapes larger than this many typical glyph sizes are frames, bubble outlines or pictures
+    pub max_glyph_size: f32,
+    /// the largest luminance difference between the background of the text block and its other pixels
+    pub background_tolerance: i32,
+}
+
+impl Default for RegionParams {
+    fn default() -> Self {
+        Self {
+            max_gap: 1.,
+            max_glyph_size: 1.5,
+            background_tolerance: 24,
+        }
+    }
+}
+
+/// bounding box of a connected component, `(x0, y0, x1, y1)` inclusive
+type Component = (i32, i32, i32, i32);
+
+fn luminance(p: &image::Rgba<u8>) -> i32 {
+    (p[0] as i32 * 299 + p[1] as i32 * 587 + p[2] as i32 * 114) / 1000
+}
+
+/// pixels on a strong luminance edge; glyphs are made of such edges whatever their color and background's
+fn edges(img: &RgbaImage) -> GrayImage {
+    const EDGE_THRESHOLD: i32 = 48;
+    let (w, h) = img.dimensions();
+    GrayImage::from_fn(w, h, |x, y| {
+        let l = luminance(img.get_pixel(x, y));
+        let right = if x + 1 < w {
+            luminance(img.get_pixel(x + 1, y))
+        } else {
+            l
+        };
+        let below = if y + 1 < h {
+            luminance(img.get_pixel(x, y + 1))
+        } else {
+            l
+        };
+        if (right - l).abs() > EDGE_THRESHOLD || (below - l).abs() > EDGE_THRESHOLD {
+            Luma([255])
+        } else {
+            Luma([0])
+        }
+    })
+}
+
+fn components(mask: &GrayImage) -> Vec<Component> {
+    let labels = connected_components(mask, Connectivity::Eight, Luma([0]));
+    let mut boxes: Vec<Option<Component>> = vec![];
+    for (x, y, label) in labels.enumerate_pixels() {
+        let label = label[0] as usize;
+        if label == 0 {
+            continue;
+        }
+        if boxes.len() < label {
+            boxes.resize(label, None);
+        }
+        let (x, y) = (x as i32, y as i32);
+        boxes[label - 1] = Some(match boxes[label - 1] {
+            Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+            None => (x, y, x, y),
+        });
+    }
+    boxes.into_iter().flatten().collect()
+}
+
+fn distance(a: &Component, b: &Component) -> i32 {
+    let dx = (a.0 - b.2).max(b.0 - a.2).max(0);
+    let dy = (a.1 - b.3).max(b.1 - a.3).max(0);
+    dx.max(dy)
+}
+
+/// bounding box of the background around `(px, py)`: the area of the median luminance of the cursor's
+/// surroundings, flood-filled from the cursor, i.e. the inside of a speech bubble or of a dialogue box
+fn background_at(img: &RgbaImage, px: i32, py: i32, tolerance: i32) -> Option<Component> {
+    const WINDOW: i32 = 15;
+    let (w, h) = (img.width() as i32, img.height() as i32);
+    let window = || {
+        ((py - WINDOW).max(0)..(py + WINDOW + 1).min(h)).flat_map(move |y| {
+            ((px - WINDOW).max(0)..(px + WINDOW + 1).min(w)).map(move |x| (x, y))
+        })
+    };
+    let lum = |(x, y): (i32, i32)| luminance(img.get_pixel(x as u32, y as u32));
+
+    let mut lums: Vec<i32> = window().map(lum).collect();
+    if lums.is_empty() {
+        return None;
+    }
+    lums.sort_unstable();
+    let background = lums[lums.len() / 2];
+    let is_background = |p: (i32, i32)| (lum(p) - background).abs() <= tolerance;
+
+    // all of the window's background as the starting point, as some of it may be enclosed in glyphs
+    let mut visited = vec![false; (w * h) as usize];
+    let mut frontier: Vec<(i32, i32)> = window().filter(|&p| is_background(p)).collect();
+    for &(x, y) in &frontier {
+        visited[(y * w + x) as usize] = true;
+    }
+    let mut bbox = (px, py, px, py);
+    while let Some((x, y)) = frontier.pop() {
+        bbox = (bbox.0.min(x), bbox.1.min(y), bbox.2.max(x), bbox.3.max(y));
+        for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+            if nx < 0 || ny < 0 || nx >= w || ny >= h || visited[(ny * w + nx) as usize] {
+                continue;
+            }
+            visited[(ny * w + nx) as usize] = true;
+            if is_background((nx, ny)) {
+                frontier.push((nx, ny));
+            }
+        }
+    }
+    Some(bbox)
+}
+
+/// the text block (a dialogue box, a speech bubble, a paragraph) around the point `(px, py)` of the image,
+/// as `(x, y, w, h)` within the image
+///
+/// glyphs are found as connected components of the edge map, within the background surrounding the point; the block
+/// is grown from the glyph nearest to the point by adding glyphs close enough to it, so blank margins bound it too
+pub fn text_block_at(
+    img: &RgbaImage,
+    px: i32,
+    py: i32,
+    params: &RegionParams,
+) -> Option<(i32, i32, i32, i32)> {
+    // glyphs outside the text's background, e.g. on the picture around a speech bubble, belong to other blocks
+    let bounds = background_at(img, px, py, params.background_tolerance)?;
+    let all: Vec<Component> = components(&edges(img))
+        .into_iter()
+        .filter(|c| c.0 >= bounds.0 && c.1 >= bounds.1 && c.2 <= bounds.2 && c.3 <= bounds.3)
+        .collect();
+    let cursor = (px, py, px, py);
+    let size = |c: &Component| std::cmp::max(c.2 - c.0, c.3 - c.1) + 1;
+
+    // typical glyph size: big enough a component around the cursor, as many are only parts of glyphs, or noise
+    let radius = std::cmp::min(img.width(), img.height()) as i32 / 4;
+    let mut sizes: Vec<i32> = all
+        .iter()
+        .filter(|c| distance(c, &cursor) <= radius)
+        .map(size)
+        .filter(|&s| s > 4)
+        .collect();
+    if sizes.is_empty() {
+        return None;
+    }
+    sizes.sort_unstable();
+    let glyph_size = sizes[sizes.len() * 9 / 10];
+
+    // whole glyphs only: parts of glyphs fall within the block anyway, frames and pictures are excluded
+    let min_size = glyph_size / 2;
+    let max_size = (params.max_glyph_size * glyph_size as f32) as i32;
+    let glyphs: Vec<Component> = all
+        .into_iter()
+        .filter(|c| (min_size..=max_size).contains(&size(c)))
+        .collect();
+
+    let max_gap = (params.max_gap * glyph_size as f32) as i32;
+    let seed = glyphs
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (i, distance(c, &cursor)))
+        .filter(|&(_, d)| d <= max_gap)
+        .min_by_key(|&(_, d)| d)?
+        .0;
+
+    let mut in_block = vec![false; glyphs.len()];
+    in_block[seed] = true;
+    let mut frontier = vec![seed];
+    while let Some(i) = frontier.pop() {
+        for (j, glyph) in glyphs.iter().enumerate() {
+            if !in_block[j] && distance(&glyphs[i], glyph) <= max_gap {
+                in_block[j] = true;
+                frontier.push(j);
+            }
+        }
+    }
+
+    let block = glyphs
+        .iter()
+        .zip(in_block)
+        .filter(|(_, in_block)| *in_block)
+        .map(|(c, _)| *c)
+        .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))?;
+
+    // some margin helps Tesseract with glyphs touching the border
+    let margin = glyph_size / 2;
+    let x0 = (block.0 - margin).max(0);
+    let y0 = (block.1 - margin).max(0);
+    let x1 = (block.2 + margin).min(img.width() as i32 - 1);
+    let y1 = (block.3 + margin).min(img.height() as i32 - 1);
+    Some((x0, y0, x1 - x0 + 1, y1 - y0 + 1))
+}
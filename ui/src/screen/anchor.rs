@@ -0,0 +1,124 @@
+use anyhow::Result;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ChangeWindowAttributesAux, ConnectionExt as _, EventMask, Window};
+use x11rb::protocol::Event;
+
+use crate::overlay::x11::top_level;
+
+/// a capture area bound to an application's window, following it when it moves or is resized
+pub struct Anchor {
+    pub window: Window,
+    /// the window's ancestor below the root, e.g. a window manager's frame, which moves along with it
+    frame: Window,
+    /// the window's geometry within the root window
+    geometry: (i32, i32, u32, u32),
+    /// the capture area relative to the window, in fractions of its size
+    area: (f32, f32, f32, f32),
+}
+
+/// the geometry of the window within the root window
+fn window_geometry<Conn>(conn: &Conn, root: Window, window: Window) -> Result<(i32, i32, u32, u32)>
+where
+    Conn: Connection,
+{
+    let geometry = conn.get_geometry(window)?.reply()?;
+    let origin = conn.translate_coordinates(window, root, 0, 0)?.reply()?;
+    Ok((
+        origin.dst_x as i32,
+        origin.dst_y as i32,
+        geometry.width as u32,
+        geometry.height as u32,
+    ))
+}
+
+impl Anchor {
+    /// bind the area `(x, y, w, h)` of the root window to the window
+    pub fn new<Conn>(
+        conn: &Conn,
+        root: Window,
+        window: Window,
+        (x, y, w, h): (i32, i32, u32, u32),
+    ) -> Result<Anchor>
+    where
+        Conn: Connection,
+    {
+        let frame = top_level(conn, root, window)?;
+        // moves of the frame are notified to the frame only, resizes to the window too
+        let values = ChangeWindowAttributesAux::new().event_mask(EventMask::STRUCTURE_NOTIFY);
+        conn.change_window_attributes(window, &values)?;
+        if frame != window {
+            conn.change_window_attributes(frame, &values)?;
+        }
+        conn.flush()?;
+
+        let geometry = window_geometry(conn, root, window)?;
+        let (gx, gy, gw, gh) = geometry;
+        let (gw, gh) = (std::cmp::max(gw, 1) as f32, std::cmp::max(gh, 1) as f32);
+        Ok(Anchor {
+            window,
+            frame,
+            geometry,
+            area: (
+                (x - gx) as f32 / gw,
+                (y - gy) as f32 / gh,
+                w as f32 / gw,
+                h as f32 / gh,
+            ),
+        })
+    }
+
+    /// stop following the window
+    pub fn release<Conn>(self, conn: &Conn) -> Result<()>
+    where
+        Conn: Connection,
+    {
+        let values = ChangeWindowAttributesAux::new().event_mask(EventMask::NO_EVENT);
+        // the window may be gone already
+        let _ = conn.change_window_attributes(self.window, &values);
+        if self.frame != self.window {
+            let _ = conn.change_window_attributes(self.frame, &values);
+        }
+        conn.flush()?;
+        Ok(())
+    }
+
+    /// whether the event may have moved or resized the window
+    pub fn concerns(&self, event: &Event) -> bool {
+        match event {
+            Event::ConfigureNotify(e) => e.window == self.window || e.window == self.frame,
+            _ => false,
+        }
+    }
+
+    /// whether the event is about the window being destroyed
+    pub fn destroyed(&self, event: &Event) -> bool {
+        match event {
+            Event::DestroyNotify(e) => e.window == self.window || e.window == self.frame,
+            _ => false,
+        }
+    }
+
+    /// update the window's geometry; whether it was moved, and whether it was resized
+    pub fn refresh<Conn>(&mut self, conn: &Conn, root: Window) -> Result<(bool, bool)>
+    where
+        Conn: Connection,
+    {
+        let geometry = window_geometry(conn, root, self.window)?;
+        let moved = (geometry.0, geometry.1) != (self.geometry.0, self.geometry.1);
+        let resized = (geometry.2, geometry.3) != (self.geometry.2, self.geometry.3);
+        self.geometry = geometry;
+        Ok((moved, resized))
+    }
+
+    /// the capture area within the root window, at the window's current geometry
+    pub fn area(&self) -> (i32, i32, u32, u32) {
+        let (gx, gy, gw, gh) = self.geometry;
+        let (fx, fy, fw, fh) = self.area;
+        (
+            gx + (fx * gw as f32).round() as i32,
+            gy + (fy * gh as f32).round() as i32,
+            (fw * gw as f32).round() as u32,
+            (fh * gh as f32).round() as u32,
+        )
+    }
+}
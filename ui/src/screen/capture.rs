@@ -0,0 +1,271 @@
+use std::fmt;
+
+use anyhow::{anyhow, Context, Result};
+use image::RgbaImage;
+use log::{debug, warn};
+use x11rb::connection::Connection;
+use x11rb::errors::ReplyError;
+use x11rb::protocol::shm::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::{ConnectionExt as _, ImageFormat, ImageOrder, Window};
+use x11rb::protocol::ErrorKind;
+
+/// how the pixels of the root window are laid out in `ZPixmap` images
+#[derive(Debug, Clone, Copy)]
+struct PixelFormat {
+    bits_per_pixel: u8,
+    scanline_pad: u8,
+    msb_first: bool,
+    red_mask: u32,
+    green_mask: u32,
+    blue_mask: u32,
+}
+
+impl PixelFormat {
+    fn bytes_per_line(&self, width: u32) -> usize {
+        let pad = self.scanline_pad as u32;
+        let bits = width * self.bits_per_pixel as u32;
+        ((bits + pad - 1) / pad * pad / 8) as usize
+    }
+
+    /// the 8 bits value of the channel selected by `mask`, whatever its width within the pixel
+    fn channel(pixel: u32, mask: u32) -> u8 {
+        if mask == 0 {
+            return 0;
+        }
+        let value = (pixel & mask) >> mask.trailing_zeros();
+        let max = mask >> mask.trailing_zeros();
+        (value * 255 / max) as u8
+    }
+
+    fn to_rgba(&self, data: &[u8], width: u32, height: u32) -> Result<RgbaImage> {
+        let bytes_per_pixel = (self.bits_per_pixel as usize + 7) / 8;
+        let bytes_per_line = self.bytes_per_line(width);
+        if data.len() < bytes_per_line * height as usize {
+            return Err(anyhow!("truncated screen capture"));
+        }
+        Ok(RgbaImage::from_fn(width, height, |x, y| {
+            let offset = y as usize * bytes_per_line + x as usize * bytes_per_pixel;
+            let bytes = &data[offset..offset + bytes_per_pixel];
+            let pixel = if self.msb_first {
+                bytes.iter().fold(0u32, |acc, &b| acc << 8 | b as u32)
+            } else {
+                bytes.iter().rev().fold(0u32, |acc, &b| acc << 8 | b as u32)
+            };
+            image::Rgba([
+                Self::channel(pixel, self.red_mask),
+                Self::channel(pixel, self.green_mask),
+                Self::channel(pixel, self.blue_mask),
+                255,
+            ])
+        }))
+    }
+}
+
+/// a failure of the shared memory itself, after which captures go through `GetImage`
+#[derive(Debug)]
+struct ShmUnusable;
+
+impl fmt::Display for ShmUnusable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MIT-SHM unusable")
+    }
+}
+
+/// the area `(x, y, w, h)` as the X protocol takes it, unless it does not fit
+fn protocol_area(x: i32, y: i32, w: u32, h: u32) -> Result<(i16, i16, u16, u16)> {
+    let too_large = || anyhow!("capture area {}x{}+{}+{} too large", w, h, x, y);
+    Ok((
+        i16::try_from(x).map_err(|_| too_large())?,
+        i16::try_from(y).map_err(|_| too_large())?,
+        u16::try_from(w).map_err(|_| too_large())?,
+        u16::try_from(h).map_err(|_| too_large())?,
+    ))
+}
+
+/// a System V shared memory segment, attached to both this process and the X server
+struct ShmSegment {
+    seg: shm::Seg,
+    addr: *mut libc::c_void,
+    size: usize,
+}
+
+impl ShmSegment {
+    fn new<Conn>(conn: &Conn, size: usize) -> Result<ShmSegment>
+    where
+        Conn: Connection,
+    {
+        // shmget fails with EINVAL rather than making an empty segment
+        if size == 0 {
+            return Err(anyhow!("empty shared memory segment"));
+        }
+        unsafe {
+            let id = libc::shmget(libc::IPC_PRIVATE, size, libc::IPC_CREAT | 0o600);
+            if id < 0 {
+                return Err(anyhow!(
+                    "shmget failed: {}",
+                    std::io::Error::last_os_error()
+                ));
+            }
+            let addr = libc::shmat(id, std::ptr::null(), libc::SHM_RDONLY);
+            if addr as isize == -1 {
+                libc::shmctl(id, libc::IPC_RMID, std::ptr::null_mut());
+                return Err(anyhow!("shmat failed: {}", std::io::Error::last_os_error()));
+            }
+            let seg = conn.generate_id()?;
+            let attached = conn
+                .shm_attach(seg, id as u32, false)
+                .map_err(anyhow::Error::from)
+                .and_then(|cookie| cookie.check().map_err(anyhow::Error::from));
+            // the segment is destroyed once both sides detach from it, even if the process dies
+            libc::shmctl(id, libc::IPC_RMID, std::ptr::null_mut());
+            if let Err(e) = attached {
+                libc::shmdt(addr);
+                return Err(e);
+            }
+            Ok(ShmSegment { seg, addr, size })
+        }
+    }
+
+    fn detach<Conn>(self, conn: &Conn)
+    where
+        Conn: Connection,
+    {
+        let _ = conn.shm_detach(self.seg);
+        unsafe {
+            libc::shmdt(self.addr);
+        }
+    }
+
+    fn data(&self, len: usize) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.addr as *const u8, len.min(self.size)) }
+    }
+}
+
+/// captures of the root window's pixels, through MIT-SHM when the X server supports it, `GetImage` otherwise
+pub struct ScreenCapture {
+    root: Window,
+    format: PixelFormat,
+    shm_available: bool,
+    segment: Option<ShmSegment>,
+}
+
+impl ScreenCapture {
+    pub fn new<Conn>(conn: &Conn, screen_num: usize) -> Result<ScreenCapture>
+    where
+        Conn: Connection,
+    {
+        let setup = conn.setup();
+        let screen = &setup.roots[screen_num];
+        let pixmap_format = setup
+            .pixmap_formats
+            .iter()
+            .find(|f| f.depth == screen.root_depth)
+            .ok_or_else(|| anyhow!("no pixmap format for depth {}", screen.root_depth))?;
+        let visual = screen
+            .allowed_depths
+            .iter()
+            .flat_map(|d| d.visuals.iter())
+            .find(|v| v.visual_id == screen.root_visual)
+            .ok_or_else(|| anyhow!("root visual not found"))?;
+        let format = PixelFormat {
+            bits_per_pixel: pixmap_format.bits_per_pixel,
+            scanline_pad: pixmap_format.scanline_pad,
+            msb_first: setup.image_byte_order == ImageOrder::MSB_FIRST,
+            red_mask: visual.red_mask,
+            green_mask: visual.green_mask,
+            blue_mask: visual.blue_mask,
+        };
+        debug!("root pixel format: {:?}", format);
+
+        // shared memory only works with a local X server
+        let shm_available = match conn.shm_query_version() {
+            Ok(cookie) => cookie.reply().is_ok(),
+            Err(_) => false,
+        };
+        if !shm_available {
+            warn!("MIT-SHM not available, falling back on slower captures");
+        }
+
+        Ok(ScreenCapture {
+            root: screen.root,
+            format,
+            shm_available,
+            segment: None,
+        })
+    }
+
+    /// the pixels of the area `(x, y, w, h)` of the root window, none for an empty area
+    pub fn capture<Conn>(
+        &mut self,
+        conn: &Conn,
+        x: i32,
+        y: i32,
+        w: u32,
+        h: u32,
+    ) -> Result<RgbaImage>
+    where
+        Conn: Connection,
+    {
+        if w == 0 || h == 0 {
+            return Ok(RgbaImage::new(w, h));
+        }
+        let area = protocol_area(x, y, w, h)?;
+
+        if self.shm_available {
+            match self.capture_shm(conn, area) {
+                Ok(img) => return Ok(img),
+                Err(e) if e.downcast_ref::<ShmUnusable>().is_none() => return Err(e),
+                Err(e) => {
+                    warn!("MIT-SHM capture failed, falling back on GetImage: {:?}", e);
+                    self.shm_available = false;
+                    if let Some(segment) = self.segment.take() {
+                        segment.detach(conn);
+                    }
+                }
+            }
+        }
+
+        let (x, y, w, h) = area;
+        let reply = conn
+            .get_image(ImageFormat::Z_PIXMAP, self.root, x, y, w, h, !0)?
+            .reply()?;
+        self.format.to_rgba(&reply.data, w.into(), h.into())
+    }
+
+    /// errors of the shared memory itself come with the `ShmUnusable` context, the others are those
+    /// `GetImage` would have had as well
+    fn capture_shm<Conn>(&mut self, conn: &Conn, area: (i16, i16, u16, u16)) -> Result<RgbaImage>
+    where
+        Conn: Connection,
+    {
+        let (x, y, w, h) = area;
+        let size = self.format.bytes_per_line(w.into()) * h as usize;
+        if self.segment.as_ref().map_or(true, |s| s.size < size) {
+            if let Some(segment) = self.segment.take() {
+                segment.detach(conn);
+            }
+            self.segment = Some(ShmSegment::new(conn, size).context(ShmUnusable)?);
+        }
+        let segment = self.segment.as_ref().unwrap();
+
+        conn.shm_get_image(
+            self.root,
+            x,
+            y,
+            w,
+            h,
+            !0,
+            ImageFormat::Z_PIXMAP.into(),
+            segment.seg,
+            0,
+        )?
+        .reply()
+        .map_err(|e| match e {
+            ReplyError::X11Error(ref error) if error.error_kind == ErrorKind::ShmBadSeg => {
+                anyhow::Error::from(e).context(ShmUnusable)
+            }
+            e => e.into(),
+        })?;
+        self.format.to_rgba(segment.data(size), w.into(), h.into())
+    }
+}
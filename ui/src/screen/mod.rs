@@ -0,0 +1,163 @@
+pub mod anchor;
+pub mod capture;
+
+use anyhow::{anyhow, Result};
+use image::{imageops, RgbaImage};
+use log::warn;
+use x11rb::connection::Connection;
+use x11rb::errors::ReplyError;
+use x11rb::protocol::randr::{ConnectionExt as _, NotifyMask};
+use x11rb::protocol::xproto::{ConnectionExt as _, Window};
+
+use capture::ScreenCapture;
+
+/// a monitor's area within the root window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Monitor {
+    pub x: i32,
+    pub y: i32,
+    pub w: u32,
+    pub h: u32,
+    pub primary: bool,
+}
+
+impl Monitor {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        self.x <= x && x < self.x + self.w as i32 && self.y <= y && y < self.y + self.h as i32
+    }
+
+    /// the part of the area `(x, y, w, h)` on this monitor
+    pub fn intersection(&self, x: i32, y: i32, w: u32, h: u32) -> Option<(i32, i32, u32, u32)> {
+        let x0 = std::cmp::max(x, self.x);
+        let y0 = std::cmp::max(y, self.y);
+        let x1 = std::cmp::min(x + w as i32, self.x + self.w as i32);
+        let y1 = std::cmp::min(y + h as i32, self.y + self.h as i32);
+        if x0 < x1 && y0 < y1 {
+            Some((x0, y0, (x1 - x0) as u32, (y1 - y0) as u32))
+        } else {
+            None
+        }
+    }
+
+    /// the top left corner of an area `w` x `h` centered on `(x, y)`, moved to fit within the monitor
+    pub fn fit(&self, x: i32, y: i32, w: u32, h: u32) -> (i32, i32) {
+        let w = std::cmp::min(w, self.w) as i32;
+        let h = std::cmp::min(h, self.h) as i32;
+        (
+            (x - w / 2).clamp(self.x, self.x + self.w as i32 - w),
+            (y - h / 2).clamp(self.y, self.y + self.h as i32 - h),
+        )
+    }
+}
+
+/// fails without RandR 1.5, in which case the root window is taken as the only monitor
+pub fn randr_init<Conn>(conn: &Conn, root: Window) -> Result<()>
+where
+    Conn: Connection,
+{
+    let version = conn.randr_query_version(1, 5)?.reply()?;
+    if (version.major_version, version.minor_version) < (1, 5) {
+        return Err(anyhow!(
+            "RandR {}.{} does not list monitors",
+            version.major_version,
+            version.minor_version
+        ));
+    }
+    // monitors being plugged, unplugged, or rearranged
+    conn.randr_select_input(
+        root,
+        NotifyMask::SCREEN_CHANGE | NotifyMask::CRTC_CHANGE | NotifyMask::OUTPUT_CHANGE,
+    )?;
+    Ok(())
+}
+
+/// the active monitors, or the whole root window when RandR cannot tell
+pub fn monitors<Conn>(conn: &Conn, root: Window) -> Result<Vec<Monitor>>
+where
+    Conn: Connection,
+{
+    let reply = conn
+        .randr_get_monitors(root, true)
+        .map_err(ReplyError::from)
+        .and_then(|cookie| cookie.reply());
+    let monitors: Vec<Monitor> = match reply {
+        Ok(reply) => reply
+            .monitors
+            .iter()
+            .map(|m| Monitor {
+                x: m.x as i32,
+                y: m.y as i32,
+                w: m.width as u32,
+                h: m.height as u32,
+                primary: m.primary,
+            })
+            .collect(),
+        Err(e) => {
+            warn!("Failed to list monitors: {:?}", e);
+            vec![]
+        }
+    };
+
+    if monitors.is_empty() {
+        let geometry = conn.get_geometry(root)?.reply()?;
+        return Ok(vec![Monitor {
+            x: 0,
+            y: 0,
+            w: geometry.width as u32,
+            h: geometry.height as u32,
+            primary: true,
+        }]);
+    }
+    Ok(monitors)
+}
+
+/// the size of the area spanning all monitors, from the root window's origin
+pub fn extent(monitors: &[Monitor]) -> (u16, u16) {
+    let w = monitors.iter().map(|m| m.x + m.w as i32).max();
+    let h = monitors.iter().map(|m| m.y + m.h as i32).max();
+    (
+        w.unwrap_or_default().clamp(0, u16::MAX as i32) as u16,
+        h.unwrap_or_default().clamp(0, u16::MAX as i32) as u16,
+    )
+}
+
+/// the monitor at `(x, y)`, or the primary one
+pub fn monitor_at(monitors: &[Monitor], x: i32, y: i32) -> Option<&Monitor> {
+    monitors
+        .iter()
+        .find(|m| m.contains(x, y))
+        .or_else(|| monitors.iter().find(|m| m.primary))
+        .or_else(|| monitors.first())
+}
+
+/// capture the area `(x, y, w, h)`, which may span several monitors; what is on no monitor is left black
+pub fn capture_area<Conn>(
+    conn: &Conn,
+    screen_capture: &mut ScreenCapture,
+    monitors: &[Monitor],
+    x: i32,
+    y: i32,
+    w: u32,
+    h: u32,
+) -> Result<RgbaImage>
+where
+    Conn: Connection,
+{
+    let parts: Vec<(i32, i32, u32, u32)> = monitors
+        .iter()
+        .filter_map(|m| m.intersection(x, y, w, h))
+        .collect();
+    // the usual case, a single capture straight from the root window
+    if let [(px, py, pw, ph)] = parts[..] {
+        if (px, py, pw, ph) == (x, y, w, h) {
+            return screen_capture.capture(conn, x, y, w, h);
+        }
+    }
+
+    let mut img = RgbaImage::from_pixel(w, h, image::Rgba([0, 0, 0, 255]));
+    for (px, py, pw, ph) in parts {
+        let part = screen_capture.capture(conn, px, py, pw, ph)?;
+        imageops::replace(&mut img, &part, (px - x) as i64, (py - y) as i64);
+    }
+    Ok(img)
+}
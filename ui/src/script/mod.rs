@@ -0,0 +1,276 @@
+/// broad categories of the characters found in Japanese text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharClass {
+    Kanji,
+    Hiragana,
+    Katakana,
+    /// 'ー' and its halfwidth form, which can follow both hiragana and katakana
+    ProlongedSoundMark,
+    /// ASCII and fullwidth digits
+    Digit,
+    Punctuation,
+    /// ASCII and fullwidth Latin letters
+    Latin,
+    Other,
+}
+
+pub fn classify(c: char) -> CharClass {
+    if is_prolonged_sound_mark(c) {
+        CharClass::ProlongedSoundMark
+    } else if is_kanji(c) {
+        CharClass::Kanji
+    } else if is_hiragana(c) {
+        CharClass::Hiragana
+    } else if is_katakana(c) {
+        CharClass::Katakana
+    } else if is_digit(c) {
+        CharClass::Digit
+    } else if is_latin(c) {
+        CharClass::Latin
+    } else if is_punctuation(c) {
+        CharClass::Punctuation
+    } else {
+        CharClass::Other
+    }
+}
+
+pub fn is_kanji(c: char) -> bool {
+    ('\u{4e00}'..='\u{9fff}').contains(&c)         // https://www.unicode.org/charts/PDF/U4E00.pdf
+        || ('\u{3400}'..='\u{4dbf}').contains(&c)  // https://www.unicode.org/charts/PDF/U3400.pdf
+        || ('\u{f900}'..='\u{faff}').contains(&c)  // https://www.unicode.org/charts/PDF/UF900.pdf
+        || ('\u{2e80}'..='\u{2eff}').contains(&c)  // https://www.unicode.org/charts/PDF/U2E80.pdf
+        || ('\u{2f00}'..='\u{2fdf}').contains(&c)  // https://www.unicode.org/charts/PDF/U2F00.pdf
+        || ('\u{20000}'..='\u{2a6df}').contains(&c)// https://www.unicode.org/charts/PDF/U20000.pdf
+        || ('\u{2a700}'..='\u{2b73f}').contains(&c)// https://www.unicode.org/charts/PDF/U2A700.pdf
+        || ('\u{2b740}'..='\u{2b81f}').contains(&c)// https://www.unicode.org/charts/PDF/U2B740.pdf
+        || ('\u{2b820}'..='\u{2ceaf}').contains(&c)// https://www.unicode.org/charts/PDF/U2B820.pdf
+        || ('\u{2ceb0}'..='\u{2ebef}').contains(&c)// https://www.unicode.org/charts/PDF/U2CEB0.pdf
+        || ('\u{2ebf0}'..='\u{2ee5f}').contains(&c)// https://www.unicode.org/charts/PDF/U2EBF0.pdf
+        || ('\u{2f800}'..='\u{2fa1f}').contains(&c)// https://www.unicode.org/charts/PDF/U2F800.pdf
+        || ('\u{30000}'..='\u{3134f}').contains(&c)// https://www.unicode.org/charts/PDF/U30000.pdf
+        || ('\u{31350}'..='\u{323af}').contains(&c)// https://www.unicode.org/charts/PDF/U31350.pdf
+        || ('\u{3005}'..='\u{3007}').contains(&c)  // 々〆〇 - https://www.unicode.org/charts/PDF/U3000.pdf
+        || c == '\u{303b}' // 〻 - https://www.unicode.org/charts/PDF/U3000.pdf
+}
+
+pub fn is_hiragana(c: char) -> bool {
+    ('\u{3041}'..='\u{3096}').contains(&c)          // https://www.unicode.org/charts/PDF/U3040.pdf
+        || ('\u{3099}'..='\u{309f}').contains(&c)   // voicing & iteration marks, ゟ
+        || ('\u{1b001}'..='\u{1b11f}').contains(&c) // https://www.unicode.org/charts/PDF/U1B000.pdf, https://www.unicode.org/charts/PDF/U1B100.pdf
+        || c == '\u{1b132}'                              // https://www.unicode.org/charts/PDF/U1B130.pdf
+        || ('\u{1b150}'..='\u{1b152}').contains(&c) // https://www.unicode.org/charts/PDF/U1B130.pdf
+}
+
+pub fn is_katakana(c: char) -> bool {
+    ('\u{30a1}'..='\u{30fa}').contains(&c)          // https://www.unicode.org/charts/PDF/U30A0.pdf
+        || ('\u{30fd}'..='\u{30ff}').contains(&c)   // iteration marks, ヿ
+        || ('\u{31f0}'..='\u{31ff}').contains(&c)   // https://www.unicode.org/charts/PDF/U31F0.pdf
+        || ('\u{ff66}'..='\u{ff6f}').contains(&c)   // https://www.unicode.org/charts/PDF/UFF00.pdf
+        || ('\u{ff71}'..='\u{ff9f}').contains(&c)   // https://www.unicode.org/charts/PDF/UFF00.pdf
+        || ('\u{1aff0}'..='\u{1affe}').contains(&c) // https://www.unicode.org/charts/PDF/U1AFF0.pdf
+        || c == '\u{1b000}'                              // https://www.unicode.org/charts/PDF/U1B000.pdf
+        || ('\u{1b120}'..='\u{1b122}').contains(&c) // https://www.unicode.org/charts/PDF/U1B100.pdf
+        || c == '\u{1b155}'                              // https://www.unicode.org/charts/PDF/U1B130.pdf
+        || ('\u{1b164}'..='\u{1b167}').contains(&c) // https://www.unicode.org/charts/PDF/U1B130.pdf
+}
+
+pub fn is_prolonged_sound_mark(c: char) -> bool {
+    c == '\u{30fc}' || c == '\u{ff70}'
+}
+
+pub fn is_digit(c: char) -> bool {
+    c.is_ascii_digit() || ('\u{ff10}'..='\u{ff19}').contains(&c)
+}
+
+pub fn is_latin(c: char) -> bool {
+    c.is_ascii_alphabetic()
+        || ('\u{ff21}'..='\u{ff3a}').contains(&c)
+        || ('\u{ff41}'..='\u{ff5a}').contains(&c)
+}
+
+pub fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation()
+        || ('\u{3000}'..='\u{303f}').contains(&c)   // https://www.unicode.org/charts/PDF/U3000.pdf
+        || c == '\u{30a0}' || c == '\u{30fb}'            // ゠・
+        || ('\u{2010}'..='\u{205e}').contains(&c)   // https://www.unicode.org/charts/PDF/U2000.pdf
+        || ('\u{fe10}'..='\u{fe19}').contains(&c)   // https://www.unicode.org/charts/PDF/UFE10.pdf
+        || ('\u{fe30}'..='\u{fe4f}').contains(&c)   // https://www.unicode.org/charts/PDF/UFE30.pdf
+        || ('\u{ff01}'..='\u{ff0f}').contains(&c)   // https://www.unicode.org/charts/PDF/UFF00.pdf
+        || ('\u{ff1a}'..='\u{ff20}').contains(&c)
+        || ('\u{ff3b}'..='\u{ff40}').contains(&c)
+        || ('\u{ff5b}'..='\u{ff65}').contains(&c)
+}
+
+/// kanji, kana and prolonged sound marks; what makes a piece of text Japanese
+pub fn is_japanese(c: char) -> bool {
+    matches!(
+        classify(c),
+        CharClass::Kanji
+            | CharClass::Hiragana
+            | CharClass::Katakana
+            | CharClass::ProlongedSoundMark
+    )
+}
+
+/// hiragana turned into katakana, other characters left untouched
+pub fn to_katakana(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '\u{3041}'..='\u{3096}' | '\u{309d}'..='\u{309e}' => {
+                char::from_u32(c as u32 + 0x60).unwrap_or(c)
+            }
+            _ => c,
+        })
+        .collect()
+}
+
+/// katakana turned into hiragana, other characters left untouched
+pub fn to_hiragana(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '\u{30a1}'..='\u{30f6}' | '\u{30fd}'..='\u{30fe}' => {
+                char::from_u32(c as u32 - 0x60).unwrap_or(c)
+            }
+            _ => c,
+        })
+        .collect()
+}
+
+/// which characters, beyond kanji and kana, may be part of Japanese text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ScriptPolicy {
+    pub digits: bool,
+    pub punctuation: bool,
+    pub prolonged_marks: bool,
+    pub latin: bool,
+}
+
+impl Default for ScriptPolicy {
+    fn default() -> Self {
+        Self {
+            digits: true,
+            punctuation: true,
+            prolonged_marks: true,
+            latin: false,
+        }
+    }
+}
+
+impl ScriptPolicy {
+    pub fn accepts_char(&self, c: char) -> bool {
+        match classify(c) {
+            CharClass::Kanji | CharClass::Hiragana | CharClass::Katakana => true,
+            CharClass::ProlongedSoundMark => self.prolonged_marks,
+            CharClass::Digit => self.digits,
+            CharClass::Punctuation => self.punctuation,
+            CharClass::Latin => self.latin,
+            CharClass::Other => false,
+        }
+    }
+
+    pub fn accepts(&self, s: &str) -> bool {
+        s.chars().all(|c| self.accepts_char(c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turns_katakana_into_hiragana() {
+        assert_eq!(to_hiragana("タベル"), "たべる");
+        assert_eq!(to_hiragana("ヴァイオリン"), "ゔぁいおりん");
+        // iteration marks have hiragana counterparts, the prolonged sound mark has none
+        assert_eq!(to_hiragana("スヽメ"), "すゝめ");
+        assert_eq!(to_hiragana("ラーメン"), "らーめん");
+        assert_eq!(to_hiragana("漢字とかな"), "漢字とかな");
+    }
+
+    #[test]
+    fn classifies_kanji_up_to_range_edges() {
+        for c in [
+            '\u{4e00}',
+            '\u{9fff}',
+            '\u{f900}',
+            '\u{faff}',
+            // radicals
+            '\u{2e80}',
+            '\u{2fdf}',
+            // extension B to H
+            '\u{20000}',
+            '\u{2a6df}',
+            '\u{2a700}',
+            '\u{2ee5f}',
+            '\u{30000}',
+            '\u{323af}',
+            // compatibility ideographs supplement
+            '\u{2f800}',
+            '\u{2fa1f}',
+            '々',
+            '〆',
+            '〇',
+            '〻',
+        ] {
+            assert!(is_kanji(c), "{:?}", c);
+            assert_eq!(classify(c), CharClass::Kanji, "{:?}", c);
+        }
+        for c in [
+            '\u{2e7f}',
+            '\u{2fe0}',
+            '\u{2a6e0}',
+            '\u{2ee60}',
+            '\u{2f7ff}',
+            '\u{2fa20}',
+            '\u{323b0}',
+            '\u{3004}',
+            '\u{3008}',
+        ] {
+            assert!(!is_kanji(c), "{:?}", c);
+        }
+        // the small ke of counters is a katakana, even though it reads as 箇
+        assert!(!is_kanji('ヶ'));
+        assert_eq!(classify('ヶ'), CharClass::Katakana);
+    }
+
+    #[test]
+    fn classifies_marks_digits_and_latin() {
+        assert_eq!(classify('ー'), CharClass::ProlongedSoundMark);
+        assert_eq!(classify('\u{ff70}'), CharClass::ProlongedSoundMark);
+        assert_eq!(classify('２'), CharClass::Digit);
+        assert_eq!(classify('2'), CharClass::Digit);
+        assert_eq!(classify('Ａ'), CharClass::Latin);
+        assert_eq!(classify('。'), CharClass::Punctuation);
+        assert_eq!(classify('・'), CharClass::Punctuation);
+        assert_eq!(classify('é'), CharClass::Other);
+    }
+
+    #[test]
+    fn policy_accepts_mixed_words() {
+        let policy = ScriptPolicy::default();
+        assert!(policy.accepts("２階"));
+        assert!(policy.accepts("ー"));
+        assert!(policy.accepts("ラーメン"));
+        assert!(!policy.accepts("ABC漢字"));
+        assert!(!policy.accepts("ＡＢＣ漢字"));
+
+        let latin = ScriptPolicy {
+            latin: true,
+            ..ScriptPolicy::default()
+        };
+        assert!(latin.accepts("ABC漢字"));
+        assert!(latin.accepts("ＡＢＣ漢字"));
+
+        let strict = ScriptPolicy {
+            digits: false,
+            punctuation: false,
+            prolonged_marks: false,
+            latin: false,
+        };
+        assert!(!strict.accepts("２階"));
+        assert!(!strict.accepts("ー"));
+        assert!(!strict.accepts("ラーメン"));
+        assert!(!strict.accepts("漢字。"));
+        assert!(strict.accepts("漢字とカナ"));
+    }
+}